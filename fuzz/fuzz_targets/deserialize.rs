@@ -0,0 +1,35 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use serde_derive::Deserialize;
+use serde_tcod_config_parser::{ast, de::Deserializer};
+
+#[derive(Deserialize)]
+#[serde(rename = "item_type")]
+#[allow(dead_code)]
+struct ItemType {
+    instance_name: String,
+    cost: Option<i32>,
+    weight: Option<f32>,
+    name: Option<String>,
+    symbol: Option<char>,
+    stackable: Option<bool>,
+    list: Option<Vec<u8>>,
+    effect: Option<Vec<Effect>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "effect")]
+#[allow(dead_code)]
+struct Effect {
+    instance_name: String,
+    cost: Option<u64>,
+}
+
+fuzz_target!(|source: &str| {
+    let _ = Deserializer::from_str::<ItemType>(source);
+    let _ = Deserializer::from_str::<Vec<ItemType>>(source);
+    let _ = Deserializer::from_str_recovering::<Vec<ItemType>>(source);
+    if let Ok(file) = ast::parse(source) {
+        let _ = Deserializer::from_file(file).deserialize_recovering::<Vec<ItemType>>();
+    }
+});
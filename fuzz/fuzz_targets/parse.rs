@@ -0,0 +1,18 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use serde_tcod_config_parser::listener::{self, Listener};
+use serde_tcod_config_parser::{ast, document::Document, lexer::Tokenizer};
+
+struct Ignore;
+
+impl Listener for Ignore {}
+
+fuzz_target!(|source: &str| {
+    let _ = Tokenizer::new(source).with_trivia(true).count();
+    let _ = ast::parse(source);
+    let _ = ast::parse_recovering(source);
+    let _ = listener::run(source, &mut Ignore);
+    if let Ok(document) = source.parse::<Document>() {
+        let _ = document.to_string();
+    }
+});
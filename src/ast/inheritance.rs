@@ -0,0 +1,373 @@
+use crate::ast::{File, Item, StructBlock, ValueKind};
+use crate::de::{suggestions, Error, Result};
+use crate::lexer::Span;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Lets struct blocks start from another struct block of the same type, e.g.
+/// `monster "Orc" { inherits = "BaseOrc" hp = 12 }` gets all the properties, flags and struct
+/// blocks of `monster "BaseOrc"` that it doesn't give itself. The property naming the struct block
+/// to inherit from, `property`, is removed.
+///
+/// The struct block inherited from must have the same parent as the one inheriting from it, e.g.
+/// both be at the top level of the file. It may inherit from another struct block itself, but
+/// not, directly or indirectly, from the one inheriting from it.
+///
+/// Inherited items keep their locations, so errors about them point at the struct block they
+/// were inherited from. The instance names of the struct blocks a struct block inherited from are
+/// recorded, see [`StructBlock::inherited_from`], so that the deserializers can name it in those
+/// errors as an [`Error::Inherited`].
+///
+/// ```
+/// use serde_tcod_config_parser::ast::{self, Item};
+///
+/// let source = r#"
+///     monster "BaseOrc" { hp = 10 undead }
+///     monster "Orc" { inherits = "BaseOrc" hp = 12 }
+/// "#;
+/// let mut file = ast::parse(source).unwrap();
+/// ast::resolve_inheritance(&mut file, "inherits").unwrap();
+///
+/// let names = file.structs[1].items.iter().map(|item| match item {
+///     Item::Property(property) => property.name,
+///     Item::Flag(flag) => flag.name,
+///     Item::Struct(block) => block.type_name,
+/// });
+/// assert_eq!(names.collect::<Vec<_>>(), ["undead", "hp"]);
+///
+/// let mut file = ast::parse(r#"monster "A" { inherits = "B" } monster "B" { inherits = "A" }"#)
+///     .unwrap();
+/// let error = ast::resolve_inheritance(&mut file, "inherits").unwrap_err();
+/// assert_eq!(error.to_string(), "Inheritance cycle monster A -> B -> A at position 56..59");
+/// ```
+///
+/// Returns an [`Error::DanglingReference`] for a struct block that is not there to inherit from,
+/// wrapped in an [`Error::Inherited`] naming the struct blocks that would have inherited from it
+/// through the one naming it, if any, and an [`Error::InheritanceCycle`] naming all the struct
+/// blocks involved for a struct block that ends up inheriting from itself.
+///
+/// [`StructBlock::inherited_from`]: struct.StructBlock.html#method.inherited_from
+/// [`Error::DanglingReference`]: ../de/enum.Error.html#variant.DanglingReference
+/// [`Error::Inherited`]: ../de/enum.Error.html#variant.Inherited
+/// [`Error::InheritanceCycle`]: ../de/enum.Error.html#variant.InheritanceCycle
+pub fn resolve_inheritance(file: &mut File<'_>, property: &str) -> Result<()> {
+    resolve_blocks(file.structs.iter_mut().collect(), property)
+}
+
+/// Resolves the inheritance between `blocks`, which have the same parent, and within each of
+/// them.
+fn resolve_blocks(mut blocks: Vec<&mut StructBlock<'_>>, property: &str) -> Result<()> {
+    for block in &mut blocks {
+        let children = block.items.iter_mut().filter_map(|item| match item {
+            Item::Struct(child) => Some(child),
+            _ => None,
+        });
+        resolve_blocks(children.collect(), property)?;
+    }
+
+    let mut instances = HashMap::new();
+    for (index, block) in blocks.iter().enumerate() {
+        if let Some(instance_name) = block.instance_name {
+            instances
+                .entry((block.type_name, instance_name))
+                .or_insert(index);
+        }
+    }
+
+    let mut resolved = vec![false; blocks.len()];
+    for index in 0..blocks.len() {
+        resolve(&mut blocks, index, property, &instances, &mut resolved)?;
+    }
+
+    Ok(())
+}
+
+/// Resolves the inheritance of `blocks[index]`, where `instances` are the indices of the blocks
+/// by type name and instance name.
+///
+/// Walks up from the block to the first ancestor that is resolved already, and then resolves the
+/// blocks on the way back down, so that long chains of inheritance don't use up the stack.
+fn resolve<'a>(
+    blocks: &mut [&mut StructBlock<'a>],
+    index: usize,
+    property: &str,
+    instances: &HashMap<(&'a str, &'a str), usize>,
+    resolved: &mut [bool],
+) -> Result<()> {
+    // The blocks on the way up with the blocks they inherit from, and where each block is on it
+    let mut path = Vec::new();
+    let mut positions = HashMap::new();
+    let mut current = index;
+    while !resolved[current] {
+        let (name, span) = match parent_name(blocks[current], property)? {
+            Some(parent) => parent,
+            None => {
+                resolved[current] = true;
+                break;
+            }
+        };
+
+        let block = &*blocks[current];
+        let chain = |end: usize| {
+            path.iter()
+                .map(|&(link, _)| link)
+                .chain(Some(end))
+                .map(|link| blocks[link].instance_name.unwrap_or_default().to_string())
+                .collect::<Vec<_>>()
+        };
+        let parent = match instances.get(&(block.type_name, name.as_str())) {
+            Some(&parent) => parent,
+            None => {
+                let names = blocks
+                    .iter()
+                    .filter(|candidate| candidate.type_name == block.type_name)
+                    .filter_map(|candidate| candidate.instance_name);
+                let error = Error::DanglingReference {
+                    did_you_mean: suggestions::closest(&name, names),
+                    name,
+                    type_name: block.type_name.to_string(),
+                    range: span,
+                };
+                return Err(match path.first() {
+                    Some(&(first, _)) => Error::Inherited {
+                        source: Box::new(error),
+                        type_name: block.type_name.to_string(),
+                        chain: chain(current),
+                        block: blocks[first].type_name_span.clone(),
+                    },
+                    None => error,
+                });
+            }
+        };
+
+        positions.insert(current, path.len());
+        if let Some(&start) = positions.get(&parent) {
+            let mut names = chain(current).split_off(start);
+            names.push(name);
+            return Err(Error::InheritanceCycle {
+                type_name: block.type_name.to_string(),
+                chain: names,
+                range: span,
+            });
+        }
+        path.push((current, parent));
+        current = parent;
+    }
+
+    for &(index, parent) in path.iter().rev() {
+        let inherited = blocks[parent]
+            .items
+            .iter()
+            .filter(|item| !overrides(blocks[index], item))
+            .cloned()
+            .collect::<Vec<_>>();
+        let ancestors = blocks[parent]
+            .ancestors
+            .push(blocks[parent].instance_name.unwrap_or_default());
+        let block = &mut *blocks[index];
+        block.items.retain(|item| !is_property(item, property));
+        block.items.splice(0..0, inherited);
+        block.ancestors = ancestors;
+        resolved[index] = true;
+    }
+
+    Ok(())
+}
+
+/// The instance names of the struct blocks a struct block inherited items from, nearest first.
+/// The struct blocks inheriting from the same struct block share the names from there on, so
+/// that long chains of inheritance take linear space.
+#[derive(Clone, Default)]
+pub(crate) struct Ancestors(Option<Arc<Ancestor>>);
+
+struct Ancestor {
+    name: String,
+    rest: Ancestors,
+}
+
+impl Ancestors {
+    /// These ancestors, preceded by `name`.
+    fn push(&self, name: &str) -> Self {
+        let name = name.to_string();
+        let rest = self.clone();
+        Ancestors(Some(Arc::new(Ancestor { name, rest })))
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &str> {
+        std::iter::successors(self.0.as_deref(), |ancestor| ancestor.rest.0.as_deref())
+            .map(|ancestor| ancestor.name.as_str())
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_none()
+    }
+}
+
+impl fmt::Debug for Ancestors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for Ancestors {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl Drop for Ancestors {
+    fn drop(&mut self) {
+        // Drop the links one after another rather than recursively
+        let mut next = self.0.take();
+        while let Some(ancestor) = next {
+            next = match Arc::try_unwrap(ancestor) {
+                Ok(mut ancestor) => ancestor.rest.0.take(),
+                Err(_) => None,
+            };
+        }
+    }
+}
+
+/// The instance name of the struct block `block` inherits from, and the location it was given
+/// at.
+fn parent_name(block: &StructBlock<'_>, property: &str) -> Result<Option<(String, Span)>> {
+    let value = block.items.iter().find_map(|item| match item {
+        Item::Property(inherits) if inherits.name == property => Some(&inherits.value),
+        _ => None,
+    });
+    match value {
+        Some(value) => match &value.kind {
+            ValueKind::String(name) => Ok(Some((name.to_string(), value.span.clone()))),
+            kind => Err(Error::InvalidType {
+                unexpected: kind.unexpected().to_string(),
+                expected: "the instance name of a struct block to inherit from".to_string(),
+                range: Some(value.span.clone()),
+            }),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Whether `item` is the property `name`.
+fn is_property(item: &Item<'_>, name: &str) -> bool {
+    matches!(item, Item::Property(property) if property.name == name)
+}
+
+/// Whether `block` gives its own version of the inherited `item`.
+fn overrides(block: &StructBlock<'_>, item: &Item<'_>) -> bool {
+    block.items.iter().any(|own| match (own, item) {
+        (Item::Property(own), Item::Property(inherited)) => own.name == inherited.name,
+        (Item::Property(own), Item::Flag(inherited)) => own.name == inherited.name,
+        (Item::Flag(own), Item::Property(inherited)) => own.name == inherited.name,
+        (Item::Flag(own), Item::Flag(inherited)) => own.name == inherited.name,
+        (Item::Struct(own), Item::Struct(inherited)) => {
+            own.type_name == inherited.type_name && own.instance_name == inherited.instance_name
+        }
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve_inheritance;
+    use crate::ast::{self, Item, ValueKind};
+    use crate::de::Error;
+
+    #[test]
+    fn chains() {
+        let source = r#"
+monster "Base" { hp = 1 speed = 2 ai { aggressive } }
+monster "Orc" { inherits = "Base" hp = 10 }
+monster "Chief" { inherits = "Orc" speed = 3 ai { } }
+"#;
+        let mut file = ast::parse(source).unwrap();
+        resolve_inheritance(&mut file, "inherits").unwrap();
+        let chief = &file.structs[2];
+        let values = chief
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::Property(property) => match property.value.kind {
+                    ValueKind::Integer(value) => format!("{} = {}", property.name, value),
+                    _ => unreachable!(),
+                },
+                Item::Flag(flag) => flag.name.to_string(),
+                Item::Struct(block) => format!("{} {}", block.type_name, block.items.len()),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, ["hp = 10", "speed = 3", "ai 0"]);
+        assert_eq!(chief.inherited_from().collect::<Vec<_>>(), ["Orc", "Base"]);
+
+        let mut file = ast::parse(
+            r#"monster "A" { inherits = "C" } monster "B" { inherits = "A" }
+monster "C" { inherits = "B" } monster "D" { inherits = "E" } monster "E" { }"#,
+        )
+        .unwrap();
+        match resolve_inheritance(&mut file, "inherits").unwrap_err() {
+            Error::InheritanceCycle {
+                type_name, chain, ..
+            } => {
+                assert_eq!(type_name, "monster");
+                assert_eq!(chain, ["A", "C", "B", "A"]);
+            }
+            error => panic!("unexpected error: {:?}", error),
+        }
+
+        let mut file = ast::parse(r#"monster "A" { parent = "Bse" } monster "Base" { }"#).unwrap();
+        assert!(matches!(
+            resolve_inheritance(&mut file, "parent"),
+            Err(Error::DanglingReference { did_you_mean, range, .. })
+                if did_you_mean == ["Base"] && range == (23..28)
+        ));
+
+        let mut file =
+            ast::parse(r#"monster "C" { parent = "B" } monster "B" { parent = "A" }"#).unwrap();
+        let error = resolve_inheritance(&mut file, "parent").unwrap_err();
+        assert_eq!(error.range(), Some(52..55));
+        match error {
+            Error::Inherited {
+                source,
+                chain,
+                block,
+                ..
+            } => {
+                assert!(matches!(*source, Error::DanglingReference { name, .. } if name == "A"));
+                assert_eq!(chain, ["C", "B"]);
+                assert_eq!(block, 0..7);
+            }
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn long_chains() {
+        let depth = 20_000;
+        let mut source = (1..depth)
+            .rev()
+            .map(|i| {
+                format!(
+                    "monster \"M{}\" {{ inherits = \"M{}\" hp = {} }}\n",
+                    i,
+                    i - 1,
+                    i
+                )
+            })
+            .collect::<String>();
+        source.push_str("monster \"M0\" { hp = 0 speed = 1 }");
+
+        let mut file = ast::parse(&source).unwrap();
+        resolve_inheritance(&mut file, "inherits").unwrap();
+        let names = ["M19998", "M19997"];
+        assert!(file.structs[0].inherited_from().take(2).eq(names));
+        assert_eq!(file.structs[0].inherited_from().count(), depth - 1);
+        assert_eq!(file.structs[0].items.len(), 2);
+
+        let source = source.replacen("\"M0\" { hp", "\"M0\" { inherits = \"M19999\" hp", 1);
+        let mut file = ast::parse(&source).unwrap();
+        match resolve_inheritance(&mut file, "inherits").unwrap_err() {
+            Error::InheritanceCycle { chain, .. } => assert_eq!(chain.len(), depth + 1),
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+}
@@ -0,0 +1,412 @@
+//! A syntax tree for libtcod config files, independent of [`serde`].
+//!
+//! This is useful for tools like linters and converters, that need to inspect a config file
+//! without knowing the types it is meant to be deserialized into.
+//!
+//! ```
+//! use serde_tcod_config_parser::ast::{self, Item, ValueKind};
+//!
+//! let file = ast::parse(r#"item_type "Sword" { cost = 10 stackable }"#).unwrap();
+//! let sword = &file.structs[0];
+//!
+//! assert_eq!(sword.type_name, "item_type");
+//! assert_eq!(sword.instance_name, Some("Sword"));
+//! match &sword.items[0] {
+//!     Item::Property(cost) => assert_eq!(cost.value.kind, ValueKind::Integer(10)),
+//!     _ => unreachable!(),
+//! }
+//! ```
+//!
+//! [`serde`]: https://crates.io/crates/serde
+use crate::de::{Error, Result};
+use crate::lexer::{next_trivia, Span, TokenKind};
+use serde::de::Unexpected;
+use std::borrow::Cow;
+
+mod inheritance;
+pub(crate) mod parser;
+
+pub use inheritance::resolve_inheritance;
+use inheritance::Ancestors;
+
+/// Parses a libtcod config file into a syntax tree.
+pub fn parse(source: &str) -> Result<File<'_>> {
+    parser::Parser::new(source).parse_file()
+}
+
+/// Parses a libtcod config file into a syntax tree, skipping over broken items instead of
+/// stopping at the first error. Returns the syntax tree of everything that could be parsed, along
+/// with all the errors encountered.
+pub fn parse_recovering(source: &str) -> (File<'_>, Vec<Error>) {
+    let mut parser = parser::Parser::new(source).recovering();
+    let file = parser.parse_file().unwrap_or_else(|_| File {
+        source,
+        structs: Vec::new(),
+        span: 0..source.len(),
+    });
+    (file, parser.into_errors())
+}
+
+/// A parsed libtcod config file.
+#[derive(Debug, PartialEq, Clone)]
+pub struct File<'a> {
+    /// The source text the file was parsed from.
+    pub source: &'a str,
+    /// The struct blocks at the top level of the file.
+    pub structs: Vec<StructBlock<'a>>,
+    /// The location of the whole file.
+    pub span: Span,
+}
+
+/// A struct block, e.g. `item_type "Sword" { ... }`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StructBlock<'a> {
+    /// The comments immediately preceding the struct block.
+    pub comments: Vec<&'a str>,
+    /// The type name of the struct, e.g. `item_type`.
+    pub type_name: &'a str,
+    /// The location of the type name.
+    pub type_name_span: Span,
+    /// The instance name of the struct, e.g. `Sword`, if one was given.
+    pub instance_name: Option<&'a str>,
+    /// The properties, flags and structs contained in the struct, in the order they were given.
+    pub items: Vec<Item<'a>>,
+    /// The location of the whole struct block.
+    pub span: Span,
+    /// The struct blocks this one inherited items from.
+    pub(crate) ancestors: Ancestors,
+}
+
+/// Something contained in a struct block.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Item<'a> {
+    /// A property, e.g. `cost = 10`.
+    Property(Property<'a>),
+    /// A flag, e.g. `stackable`.
+    Flag(Flag<'a>),
+    /// A struct, e.g. `effect { ... }`.
+    Struct(StructBlock<'a>),
+}
+
+impl<'a> StructBlock<'a> {
+    /// The text of the comments immediately preceding the struct block, without the comment
+    /// markers.
+    pub fn doc(&self) -> Option<String> {
+        doc_text(&self.comments)
+    }
+
+    /// The instance names of the struct blocks this one inherited items from, starting with the
+    /// one it names itself. Empty unless [`resolve_inheritance`] was run on it.
+    ///
+    /// [`resolve_inheritance`]: fn.resolve_inheritance.html
+    pub fn inherited_from(&self) -> impl Iterator<Item = &str> {
+        self.ancestors.iter()
+    }
+}
+
+impl<'a> Property<'a> {
+    /// The text of the comments immediately preceding the property, without the comment markers.
+    pub fn doc(&self) -> Option<String> {
+        doc_text(&self.comments)
+    }
+}
+
+impl<'a> Flag<'a> {
+    /// The text of the comments immediately preceding the flag, without the comment markers.
+    pub fn doc(&self) -> Option<String> {
+        doc_text(&self.comments)
+    }
+}
+
+impl<'a> Item<'a> {
+    /// The text of the comments immediately preceding the item, without the comment markers.
+    pub fn doc(&self) -> Option<String> {
+        match self {
+            Item::Property(property) => property.doc(),
+            Item::Flag(flag) => flag.doc(),
+            Item::Struct(block) => block.doc(),
+        }
+    }
+
+    /// The location of the whole item.
+    pub fn span(&self) -> &Span {
+        match self {
+            Item::Property(property) => &property.span,
+            Item::Flag(flag) => &flag.span,
+            Item::Struct(block) => &block.span,
+        }
+    }
+}
+
+/// A property, e.g. `cost = 10`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Property<'a> {
+    /// The comments immediately preceding the property.
+    pub comments: Vec<&'a str>,
+    /// The name of the property.
+    pub name: &'a str,
+    /// The location of the name of the property.
+    pub name_span: Span,
+    /// The value of the property.
+    pub value: Value<'a>,
+    /// The location of the whole property.
+    pub span: Span,
+}
+
+/// A flag, e.g. `stackable`. A flag is a boolean property that is `true` when present.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Flag<'a> {
+    /// The comments immediately preceding the flag.
+    pub comments: Vec<&'a str>,
+    /// The name of the flag.
+    pub name: &'a str,
+    /// The location of the flag.
+    pub span: Span,
+}
+
+/// The value of a property or list element.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Value<'a> {
+    /// The kind of value, and the value itself.
+    pub kind: ValueKind<'a>,
+    /// The location of the value.
+    pub span: Span,
+}
+
+/// The different kinds of values.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ValueKind<'a> {
+    /// A string. Strings that span several lines are joined together, and therefore owned.
+    String(Cow<'a, str>),
+    /// A char.
+    Char(char),
+    /// An integer, given either in decimal or hexadecimal notation.
+    Integer(i64),
+    /// A floating point number.
+    Float(f64),
+    /// A color.
+    Color(Color),
+    /// A list of values.
+    List(ListValue<'a>),
+}
+
+impl<'a> ValueKind<'a> {
+    /// Describes the value the way `serde` does in its errors.
+    pub(crate) fn unexpected(&self) -> Unexpected<'_> {
+        match self {
+            ValueKind::String(string) => Unexpected::Str(string),
+            ValueKind::Char(c) => Unexpected::Char(*c),
+            ValueKind::Integer(value) => Unexpected::Signed(*value),
+            ValueKind::Float(value) => Unexpected::Float(*value),
+            ValueKind::Color(_) => Unexpected::Other("color"),
+            ValueKind::List(_) => Unexpected::Seq,
+        }
+    }
+}
+
+/// A list of values, e.g. `[1, 2, 3]`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ListValue<'a> {
+    /// The values in the list.
+    pub values: Vec<Value<'a>>,
+    /// The location of the whole list.
+    pub span: Span,
+}
+
+/// A color, e.g. `#FF8000`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Color {
+    /// The red component.
+    pub r: u8,
+    /// The green component.
+    pub g: u8,
+    /// The blue component.
+    pub b: u8,
+}
+
+/// Finds the comments in `source[start..end]` that document whatever follows `end`, i.e. the
+/// comments that are on lines of their own, and that are not separated from what follows by a
+/// blank line.
+pub(crate) fn doc_comments(source: &str, start: usize, end: usize) -> Vec<&str> {
+    let mut comments = Vec::new();
+    let mut own_line = start == 0;
+    let mut position = start;
+    while position < end {
+        let (kind, span) = next_trivia(source, position, end);
+        let slice = &source[span.clone()];
+        if kind == TokenKind::Whitespace {
+            let lines = slice.matches('\n').count();
+            if lines > 1 {
+                comments.clear();
+            }
+            own_line |= lines > 0;
+        } else {
+            if own_line {
+                comments.push(slice);
+            }
+            own_line = false;
+        }
+        position = span.end;
+    }
+
+    comments
+}
+
+/// Turns doc comments into text by stripping the comment markers.
+pub(crate) fn doc_text(comments: &[&str]) -> Option<String> {
+    if comments.is_empty() {
+        return None;
+    }
+
+    let mut lines = Vec::new();
+    for comment in comments {
+        if let Some(comment) = comment.strip_prefix("//") {
+            lines.push(comment.trim());
+        } else {
+            let comment = &comment[2..comment.len() - 2];
+            for line in comment.lines() {
+                let line = line.trim();
+                let line = line.strip_prefix('*').unwrap_or(line);
+                lines.push(line.trim());
+            }
+            while lines.last() == Some(&"") {
+                lines.pop();
+            }
+        }
+    }
+
+    let start = lines.iter().position(|line| !line.is_empty())?;
+    Some(lines[start..].join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_nested() {
+        let source = "outer \"a\" {\n  inner { x = [1, 2.5] }\n  flag\n  s = \"b\" \"c\"\n}";
+        let file = parse(source).unwrap();
+
+        assert_eq!(file.span, 0..source.len());
+        assert_eq!(file.structs.len(), 1);
+
+        let outer = &file.structs[0];
+        assert_eq!(outer.type_name, "outer");
+        assert_eq!(outer.instance_name, Some("a"));
+        assert_eq!(outer.span, 0..source.len());
+        assert_eq!(outer.items.len(), 3);
+
+        match &outer.items[0] {
+            Item::Struct(inner) => {
+                assert_eq!(inner.type_name, "inner");
+                assert_eq!(inner.instance_name, None);
+                assert_eq!(&source[inner.span.clone()], "inner { x = [1, 2.5] }");
+                match &inner.items[0] {
+                    Item::Property(x) => {
+                        assert_eq!(&source[x.span.clone()], "x = [1, 2.5]");
+                        match &x.value.kind {
+                            ValueKind::List(list) => {
+                                assert_eq!(list.values[0].kind, ValueKind::Integer(1));
+                                assert_eq!(list.values[1].kind, ValueKind::Float(2.5));
+                            }
+                            kind => panic!("unexpected value: {:?}", kind),
+                        }
+                    }
+                    item => panic!("unexpected item: {:?}", item),
+                }
+            }
+            item => panic!("unexpected item: {:?}", item),
+        }
+
+        match &outer.items[1] {
+            Item::Flag(flag) => assert_eq!(flag.name, "flag"),
+            item => panic!("unexpected item: {:?}", item),
+        }
+
+        match &outer.items[2] {
+            Item::Property(s) => {
+                assert_eq!(s.value.kind, ValueKind::String("bc".into()));
+                assert_eq!(&source[s.value.span.clone()], "\"b\" \"c\"");
+            }
+            item => panic!("unexpected item: {:?}", item),
+        }
+    }
+
+    #[test]
+    fn parse_values() {
+        let file = parse("a { b = 0x1F c = -0x1 d = '\\n' e = #FF8000 f = -7 }").unwrap();
+        let values = file.structs[0]
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::Property(property) => property.value.kind.clone(),
+                item => panic!("unexpected item: {:?}", item),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            values,
+            vec![
+                ValueKind::Integer(31),
+                ValueKind::Integer(-1),
+                ValueKind::Char('\n'),
+                ValueKind::Color(Color {
+                    r: 255,
+                    g: 128,
+                    b: 0
+                }),
+                ValueKind::Integer(-7),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_error() {
+        assert!(parse("a { b = }").is_err());
+        assert!(parse("a { b = 1").is_err());
+        assert!(parse("a { b = 99999999999999999999 }").is_err());
+    }
+
+    #[test]
+    fn parse_recovering() {
+        let source = "a { b = } c { d = 1 e = [1, } f { g = 2 h }";
+        let (file, errors) = super::parse_recovering(source);
+
+        assert_eq!(errors.len(), 2);
+        let names = file
+            .structs
+            .iter()
+            .map(|block| block.type_name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "c", "f"]);
+        assert_eq!(file.structs[1].items.len(), 1);
+        assert_eq!(file.structs[2].items.len(), 2);
+    }
+
+    #[test]
+    fn doc_comments() {
+        let source = "// Not a doc comment
+
+// The sword.
+/* Sharp. */
+item_type \"Sword\" {
+    cost = 10 // In gold, not documentation for damage.
+    /**
+     * Damage
+     * dealt.
+     */
+    damage = 5
+    // Separated by a blank line.
+
+    stackable
+}";
+        let file = parse(source).unwrap();
+        let sword = &file.structs[0];
+
+        assert_eq!(sword.doc().as_deref(), Some("The sword.\nSharp."));
+        assert_eq!(sword.items[0].doc(), None);
+        assert_eq!(sword.items[1].doc().as_deref(), Some("Damage\ndealt."));
+        assert_eq!(sword.items[2].doc(), None);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::ast::{
+    doc_comments, Color, File, Flag, Item, ListValue, Property, StructBlock, Value, ValueKind,
+};
+use crate::de::{parse_char, Error, Result, MAX_DEPTH};
+use crate::lexer::{Span, Token};
+use crate::listener::Listener;
+use logos::{Lexer, Logos};
+use std::borrow::Cow;
+
+pub(crate) struct Parser<'a> {
+    lexer: Lexer<Token, &'a str>,
+    previous_end: usize,
+    /// How many struct blocks and lists the current token is within.
+    depth: usize,
+    /// The errors recovered from so far, or `None` to stop at the first error.
+    errors: Option<Vec<Error>>,
+}
+
+impl<'a> Parser<'a> {
+    pub(crate) fn new(source: &'a str) -> Self {
+        Self::at(Token::lexer(source))
+    }
+
+    /// Creates a parser that continues from the current token of `lexer`, e.g. to parse a single
+    /// value. The lexer can be taken back with `into_lexer`.
+    pub(crate) fn at(lexer: Lexer<Token, &'a str>) -> Self {
+        Self {
+            lexer,
+            previous_end: 0,
+            depth: 0,
+            errors: None,
+        }
+    }
+
+    pub(crate) fn into_lexer(self) -> Lexer<Token, &'a str> {
+        self.lexer
+    }
+
+    /// Starts out `depth` struct blocks and lists deep, for parsing part of a file.
+    pub(crate) fn nested_within(&mut self, depth: usize) {
+        self.depth = depth;
+    }
+
+    /// Keep parsing after errors, skipping the broken items. The errors can be retrieved with
+    /// `into_errors`.
+    pub(crate) fn recovering(mut self) -> Self {
+        self.errors = Some(Vec::new());
+        self
+    }
+
+    pub(crate) fn into_errors(self) -> Vec<Error> {
+        self.errors.unwrap_or_default()
+    }
+
+    /// Records `error` if recovering from errors, or returns it otherwise.
+    fn recover(&mut self, error: Error) -> Result {
+        match &mut self.errors {
+            Some(errors) => {
+                errors.push(error);
+                Ok(())
+            }
+            None => Err(error),
+        }
+    }
+
+    /// Skips past the tokens of a broken item, up to the start of the next item, or, if
+    /// `in_block`, the `}` closing the block.
+    fn skip_item(&mut self, in_block: bool) {
+        let mut depth = 0usize;
+        let mut first = true;
+        loop {
+            match self.lexer.token {
+                Token::EndOfProgram => return,
+                Token::Identifier if depth == 0 && !first => return,
+                Token::BraceClose if depth == 0 && in_block => return,
+                Token::BraceOpen | Token::BracketOpen => depth += 1,
+                Token::BraceClose | Token::BracketClose => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            first = false;
+            self.advance();
+        }
+    }
+
+    fn advance(&mut self) {
+        self.previous_end = self.lexer.range().end;
+        self.lexer.advance();
+    }
+
+    /// Runs `parse` on a struct block or list, which must not be nested too deeply.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth == MAX_DEPTH {
+            return Err(Error::NestingTooDeep {
+                range: self.lexer.range(),
+            });
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// The doc comments leading up to the current token.
+    fn comments(&self) -> Vec<&'a str> {
+        doc_comments(
+            self.lexer.source,
+            self.previous_end,
+            self.lexer.range().start,
+        )
+    }
+
+    pub(crate) fn parse_file(&mut self) -> Result<File<'a>> {
+        let mut structs = Vec::new();
+        while self.lexer.token != Token::EndOfProgram {
+            let result = if self.lexer.token == Token::Identifier {
+                self.parse_struct()
+            } else {
+                unexpected_token!(self.lexer, "<typename>")
+            };
+            match result {
+                Ok(block) => structs.push(block),
+                Err(error) => {
+                    self.recover(error)?;
+                    self.skip_item(false);
+                }
+            }
+        }
+
+        Ok(File {
+            source: self.lexer.source,
+            structs,
+            span: 0..self.lexer.source.len(),
+        })
+    }
+
+    /// Parses the type name, the optional instance name and the `{` of a struct block.
+    fn parse_struct_header(&mut self) -> Result<(&'a str, Span, Option<&'a str>)> {
+        let type_name = self.lexer.slice();
+        let type_name_span = self.lexer.range();
+        self.advance();
+
+        let mut instance_name = None;
+        if self.lexer.token == Token::Text {
+            let slice = self.lexer.slice();
+            instance_name = Some(&slice[1..][..slice.len() - 2]);
+            self.advance();
+        }
+
+        if self.lexer.token != Token::BraceOpen {
+            return unexpected_token!(self.lexer, "\"<instance_name>\" or {");
+        }
+        self.advance();
+
+        Ok((type_name, type_name_span, instance_name))
+    }
+
+    fn parse_struct(&mut self) -> Result<StructBlock<'a>> {
+        self.nested(Self::parse_struct_body)
+    }
+
+    fn parse_struct_body(&mut self) -> Result<StructBlock<'a>> {
+        let comments = self.comments();
+        let (type_name, type_name_span, instance_name) = self.parse_struct_header()?;
+
+        let mut items = Vec::new();
+        loop {
+            let result = match self.lexer.token {
+                Token::BraceClose => break,
+                Token::Identifier => self.parse_item(),
+                _ => unexpected_token!(self.lexer, "<field> or }"),
+            };
+            match result {
+                Ok(item) => items.push(item),
+                Err(error) => {
+                    self.recover(error)?;
+                    if self.lexer.token == Token::EndOfProgram {
+                        break;
+                    }
+                    self.skip_item(true);
+                }
+            }
+        }
+
+        let span = type_name_span.start..self.lexer.range().end;
+        self.advance();
+
+        Ok(StructBlock {
+            comments,
+            type_name,
+            type_name_span,
+            instance_name,
+            items,
+            span,
+            ancestors: Default::default(),
+        })
+    }
+
+    fn parse_item(&mut self) -> Result<Item<'a>> {
+        let comments = self.comments();
+        let name = self.lexer.slice();
+        let name_span = self.lexer.range();
+
+        let mut next = self.lexer.clone();
+        next.advance();
+        match next.token {
+            Token::Assign => {
+                self.advance();
+                self.advance();
+                let value = self.parse_value()?;
+                Ok(Item::Property(Property {
+                    comments,
+                    name,
+                    span: name_span.start..value.span.end,
+                    name_span,
+                    value,
+                }))
+            }
+            Token::Text | Token::BraceOpen => self.parse_struct().map(Item::Struct),
+            Token::Identifier | Token::BraceClose => {
+                self.advance();
+                Ok(Item::Flag(Flag {
+                    comments,
+                    name,
+                    span: name_span,
+                }))
+            }
+            _ => unexpected_token!(next, "= or \"<name>\""),
+        }
+    }
+
+    /// Parses the file, reporting what is found to `listener` as it goes instead of building a
+    /// syntax tree. Stops early, returning `false`, if the listener asks to.
+    pub(crate) fn run(&mut self, listener: &mut impl Listener) -> Result<bool> {
+        while self.lexer.token != Token::EndOfProgram {
+            if self.lexer.token != Token::Identifier {
+                return unexpected_token!(self.lexer, "<typename>");
+            }
+            if !self.run_struct(listener)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn run_struct(&mut self, listener: &mut impl Listener) -> Result<bool> {
+        self.nested(|parser| parser.run_struct_body(listener))
+    }
+
+    fn run_struct_body(&mut self, listener: &mut impl Listener) -> Result<bool> {
+        let (type_name, type_name_span, instance_name) = self.parse_struct_header()?;
+        if !listener.parser_new_struct(type_name, instance_name, type_name_span.clone()) {
+            return Ok(false);
+        }
+
+        loop {
+            match self.lexer.token {
+                Token::BraceClose => break,
+                Token::Identifier => {}
+                _ => return unexpected_token!(self.lexer, "<field> or }"),
+            }
+
+            let name = self.lexer.slice();
+            let name_span = self.lexer.range();
+            let mut next = self.lexer.clone();
+            next.advance();
+            let keep_going = match next.token {
+                Token::Assign => {
+                    self.advance();
+                    self.advance();
+                    let value = self.parse_value()?;
+                    let span = name_span.start..value.span.end;
+                    listener.parser_property(name, &value, span)
+                }
+                Token::Text | Token::BraceOpen => self.run_struct(listener)?,
+                Token::Identifier | Token::BraceClose => {
+                    self.advance();
+                    listener.parser_flag(name, name_span)
+                }
+                _ => return unexpected_token!(next, "= or \"<name>\""),
+            };
+            if !keep_going {
+                return Ok(false);
+            }
+        }
+
+        let span = type_name_span.start..self.lexer.range().end;
+        self.advance();
+        Ok(listener.parser_end_struct(type_name, instance_name, span))
+    }
+
+    pub(crate) fn parse_value(&mut self) -> Result<Value<'a>> {
+        let span = self.lexer.range();
+        let slice = self.lexer.slice();
+
+        let kind = match self.lexer.token {
+            Token::Text => {
+                let mut result = Cow::Borrowed(&slice[1..][..slice.len() - 2]);
+                let mut end = span.end;
+                self.advance();
+                while self.lexer.token == Token::Text {
+                    let slice = self.lexer.slice();
+                    result.to_mut().push_str(&slice[1..][..slice.len() - 2]);
+                    end = self.lexer.range().end;
+                    self.advance();
+                }
+
+                return Ok(Value {
+                    kind: ValueKind::String(result),
+                    span: span.start..end,
+                });
+            }
+            Token::Char => ValueKind::Char(parse_char(Token::Char, slice)?),
+            Token::Integer => ValueKind::Integer(self.parse_number(slice, 10)?),
+            Token::Hex => {
+                let (negative, digits) = match slice.strip_prefix('-') {
+                    Some(digits) => (true, digits),
+                    None => (false, slice),
+                };
+                let value = self.parse_number(&digits[2..], 16)?;
+                ValueKind::Integer(if negative { -value } else { value })
+            }
+            Token::Float => match slice.parse() {
+                Ok(value) => ValueKind::Float(value),
+                Err(_) => return Err(self.invalid_number()),
+            },
+            Token::Color => {
+                let component = |i| u8::from_str_radix(&slice[i..i + 2], 16).unwrap_or(0);
+                ValueKind::Color(Color {
+                    r: component(1),
+                    g: component(3),
+                    b: component(5),
+                })
+            }
+            Token::BracketOpen => self.nested(Self::parse_list)?,
+            _ => return unexpected_token!(self.lexer, "<value>"),
+        };
+
+        let span = span.start..self.lexer.range().end;
+        self.advance();
+
+        Ok(Value { kind, span })
+    }
+
+    /// Parses the values of the list starting at the current `[`, up to the closing `]`.
+    fn parse_list(&mut self) -> Result<ValueKind<'a>> {
+        let start = self.lexer.range().start;
+        self.advance();
+        let mut values = Vec::new();
+        while self.lexer.token != Token::BracketClose {
+            values.push(self.parse_value()?);
+            match self.lexer.token {
+                Token::Comma => self.advance(),
+                Token::BracketClose => {}
+                _ => return unexpected_token!(self.lexer, "<value> or ]"),
+            }
+        }
+
+        Ok(ValueKind::List(ListValue {
+            values,
+            span: start..self.lexer.range().end,
+        }))
+    }
+
+    fn parse_number(&self, digits: &str, radix: u32) -> Result<i64> {
+        i64::from_str_radix(digits, radix).map_err(|_| self.invalid_number())
+    }
+
+    fn invalid_number(&self) -> Error {
+        Error::InvalidNumber {
+            value: self.lexer.slice().to_string(),
+            range: self.lexer.range(),
+        }
+    }
+}
@@ -0,0 +1,409 @@
+//! libtcod-style typed getters on a parsed config file, for when defining [`serde`] types is
+//! overkill.
+//!
+//! Properties are addressed by paths like `item_type#Sword.cost`, where each segment but the last
+//! names a struct block by its type name, optionally followed by `#` and an instance name. Without
+//! an instance name, the first struct block of that type is used.
+//!
+//! ```
+//! use serde_tcod_config_parser::config::ParsedConfig;
+//!
+//! let source = r#"
+//!     item_type "Sword" { cost = 10 damage = [2, 5] stackable }
+//!     item_type "Bow" { cost = 7 }
+//! "#;
+//! let config = ParsedConfig::parse(source).unwrap();
+//!
+//! assert_eq!(config.get_int_property("item_type.cost").unwrap(), 10);
+//! assert_eq!(config.get_int_property("item_type#Bow.cost").unwrap(), 7);
+//! assert_eq!(config.get::<Vec<i32>>("item_type#Sword.damage").unwrap(), vec![2, 5]);
+//! assert!(config.get_bool_property("item_type#Sword.stackable").unwrap());
+//! assert!(!config.get_bool_property("item_type#Bow.stackable").unwrap());
+//! assert!(config.get_string_property("item_type#Bow.cost").is_err());
+//! ```
+//!
+//! [`serde`]: https://crates.io/crates/serde
+use crate::ast::{self, Color, File, Item, Property, StructBlock, Value, ValueKind};
+use crate::de::{Error, Result};
+use crate::path::{self, Segment};
+
+/// A parsed config file, with getters for its properties like those of libtcod's `TCODParser`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParsedConfig<'a> {
+    file: File<'a>,
+}
+
+/// A type that can be read from a property with [`ParsedConfig::get`].
+///
+/// [`ParsedConfig::get`]: struct.ParsedConfig.html#method.get
+pub trait FromProperty<'c>: Sized {
+    /// A description of the values this type can be read from, for error messages.
+    const EXPECTED: &'static str;
+
+    /// Reads the value, or tells why it cannot be read as this type.
+    fn from_value(value: &'c ValueKind<'_>) -> std::result::Result<Self, Mismatch>;
+
+    /// The value of a flag that was given, or `None` if a flag cannot be read as this type.
+    fn from_flag() -> Option<Self> {
+        None
+    }
+}
+
+/// Why a value cannot be read as a [`FromProperty`] type.
+///
+/// [`FromProperty`]: trait.FromProperty.html
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Mismatch {
+    /// The value is of the wrong type, e.g. a string for an integer.
+    Type,
+    /// The value is of the right type, but out of range, e.g. a negative number for a `u32`.
+    Range,
+}
+
+impl<'a> ParsedConfig<'a> {
+    /// Parses a libtcod config file.
+    pub fn parse(source: &'a str) -> Result<Self> {
+        ast::parse(source).map(Self::from)
+    }
+
+    /// The syntax tree of the file.
+    pub fn file(&self) -> &File<'a> {
+        &self.file
+    }
+
+    /// The struct block at `path`, if there is one.
+    pub fn get_struct(&self, path: &str) -> Option<&StructBlock<'a>> {
+        self.block(&path::segments(path))
+    }
+
+    /// The value of the property or flag at `path`.
+    ///
+    /// Returns an [`Error::PathNotFound`] if there is nothing at `path`, an [`Error::InvalidType`]
+    /// pointing at the value if it is not of type `T`, and an [`Error::InvalidValue`] pointing at
+    /// the value if it is out of range for `T`.
+    ///
+    /// [`Error::PathNotFound`]: ../de/enum.Error.html#variant.PathNotFound
+    /// [`Error::InvalidType`]: ../de/enum.Error.html#variant.InvalidType
+    /// [`Error::InvalidValue`]: ../de/enum.Error.html#variant.InvalidValue
+    pub fn get<'c, T: FromProperty<'c>>(&'c self, path: &str) -> Result<T> {
+        read(self.item(path)?)
+    }
+
+    /// The value of the property or flag at `path`, or `default` if there is nothing there.
+    /// Values of the wrong type are still an error.
+    pub fn get_or<'c, T: FromProperty<'c>>(&'c self, path: &str, default: T) -> Result<T> {
+        match self.get(path) {
+            Err(Error::PathNotFound { .. }) => Ok(default),
+            result => result,
+        }
+    }
+
+    /// Whether the flag at `path` is given. Like in libtcod, a missing flag is `false`, but a
+    /// missing struct block is still an [`Error::PathNotFound`].
+    ///
+    /// [`Error::PathNotFound`]: ../de/enum.Error.html#variant.PathNotFound
+    pub fn get_bool_property(&self, path: &str) -> Result<bool> {
+        match self.find_item(path)? {
+            Some(item) => read(item),
+            None => Ok(false),
+        }
+    }
+
+    /// The char property at `path`.
+    pub fn get_char_property(&self, path: &str) -> Result<char> {
+        self.get(path)
+    }
+
+    /// The integer property at `path`.
+    pub fn get_int_property(&self, path: &str) -> Result<i32> {
+        self.get(path)
+    }
+
+    /// The floating point property at `path`.
+    pub fn get_float_property(&self, path: &str) -> Result<f32> {
+        self.get(path)
+    }
+
+    /// The string property at `path`.
+    pub fn get_string_property(&self, path: &str) -> Result<&str> {
+        self.get(path)
+    }
+
+    /// The color property at `path`.
+    pub fn get_color_property(&self, path: &str) -> Result<Color> {
+        self.get(path)
+    }
+
+    /// The values of the list property at `path`. Use [`get`] with a `Vec` to read all the
+    /// values as a single type.
+    ///
+    /// [`get`]: #method.get
+    pub fn get_list_property(&self, path: &str) -> Result<&[Value<'a>]> {
+        match self.item(path)? {
+            Item::Property(Property {
+                value:
+                    Value {
+                        kind: ValueKind::List(list),
+                        ..
+                    },
+                ..
+            }) => Ok(&list.values),
+            item => Err(mismatch_error(item, "a list", Mismatch::Type)),
+        }
+    }
+
+    /// The property or flag at `path`.
+    fn item(&self, path: &str) -> Result<&Item<'a>> {
+        self.find_item(path)?.ok_or_else(|| not_found(path))
+    }
+
+    /// The property or flag at `path`, if the struct block it would be in exists.
+    fn find_item(&self, path: &str) -> Result<Option<&Item<'a>>> {
+        let mut segments = path::segments(path);
+        let last = segments.pop().ok_or_else(|| not_found(path))?;
+        let block = self.block(&segments).ok_or_else(|| not_found(path))?;
+        Ok(block.items.iter().find(|item| match item {
+            Item::Property(property) => property.name == last.name,
+            Item::Flag(flag) => flag.name == last.name,
+            Item::Struct(_) => false,
+        }))
+    }
+
+    fn block(&self, segments: &[Segment]) -> Option<&StructBlock<'a>> {
+        let (first, rest) = segments.split_first()?;
+        let mut block = self
+            .file
+            .structs
+            .iter()
+            .find(|block| first.matches(block.type_name, block.instance_name))?;
+        for segment in rest {
+            block = block.items.iter().find_map(|item| match item {
+                Item::Struct(child) if segment.matches(child.type_name, child.instance_name) => {
+                    Some(child)
+                }
+                _ => None,
+            })?;
+        }
+
+        Some(block)
+    }
+}
+
+/// The error for a path that leads nowhere.
+fn not_found(path: &str) -> Error {
+    Error::PathNotFound {
+        path: path.to_string(),
+    }
+}
+
+/// Reads the property or flag `item` as a `T`.
+fn read<'c, T: FromProperty<'c>>(item: &'c Item<'_>) -> Result<T> {
+    let result = match item {
+        Item::Property(property) => T::from_value(&property.value.kind),
+        Item::Flag(_) => T::from_flag().ok_or(Mismatch::Type),
+        Item::Struct(_) => Err(Mismatch::Type),
+    };
+    result.map_err(|mismatch| mismatch_error(item, T::EXPECTED, mismatch))
+}
+
+/// The error for a property or flag that cannot be read as the `expected` type.
+fn mismatch_error(item: &Item<'_>, expected: &str, mismatch: Mismatch) -> Error {
+    let unexpected = match item {
+        Item::Property(property) => property.value.kind.unexpected().to_string(),
+        Item::Flag(_) => "flag".to_string(),
+        Item::Struct(_) => "struct block".to_string(),
+    };
+    let expected = expected.to_string();
+    let range = Some(match item {
+        Item::Property(property) => property.value.span.clone(),
+        _ => item.span().clone(),
+    });
+    match mismatch {
+        Mismatch::Type => Error::InvalidType {
+            unexpected,
+            expected,
+            range,
+        },
+        Mismatch::Range => Error::InvalidValue {
+            unexpected,
+            expected,
+            range,
+        },
+    }
+}
+
+impl<'a> From<File<'a>> for ParsedConfig<'a> {
+    fn from(file: File<'a>) -> Self {
+        Self { file }
+    }
+}
+
+impl<'c> FromProperty<'c> for bool {
+    const EXPECTED: &'static str = "a flag";
+
+    fn from_value(_: &'c ValueKind<'_>) -> std::result::Result<Self, Mismatch> {
+        Err(Mismatch::Type)
+    }
+
+    fn from_flag() -> Option<Self> {
+        Some(true)
+    }
+}
+
+impl<'c> FromProperty<'c> for char {
+    const EXPECTED: &'static str = "a char";
+
+    fn from_value(value: &'c ValueKind<'_>) -> std::result::Result<Self, Mismatch> {
+        match *value {
+            ValueKind::Char(c) => Ok(c),
+            ValueKind::Integer(value) if (0..=255).contains(&value) => Ok(value as u8 as char),
+            ValueKind::Integer(_) => Err(Mismatch::Range),
+            _ => Err(Mismatch::Type),
+        }
+    }
+}
+
+macro_rules! integer_property {
+    ($($ty: ty => $expected: expr),*) => {
+        $(
+            impl<'c> FromProperty<'c> for $ty {
+                const EXPECTED: &'static str = $expected;
+
+                fn from_value(value: &'c ValueKind<'_>) -> std::result::Result<Self, Mismatch> {
+                    match *value {
+                        ValueKind::Integer(value) => {
+                            std::convert::TryFrom::try_from(value).map_err(|_| Mismatch::Range)
+                        }
+                        _ => Err(Mismatch::Type),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+integer_property! {
+    i32 => "a 32-bit integer",
+    i64 => "an integer",
+    u32 => "a non-negative 32-bit integer",
+    u64 => "a non-negative integer"
+}
+
+impl<'c> FromProperty<'c> for f32 {
+    const EXPECTED: &'static str = "a number";
+
+    fn from_value(value: &'c ValueKind<'_>) -> std::result::Result<Self, Mismatch> {
+        f64::from_value(value).map(|value| value as f32)
+    }
+}
+
+impl<'c> FromProperty<'c> for f64 {
+    const EXPECTED: &'static str = "a number";
+
+    fn from_value(value: &'c ValueKind<'_>) -> std::result::Result<Self, Mismatch> {
+        match *value {
+            ValueKind::Float(value) => Ok(value),
+            ValueKind::Integer(value) => Ok(value as f64),
+            _ => Err(Mismatch::Type),
+        }
+    }
+}
+
+impl<'c> FromProperty<'c> for &'c str {
+    const EXPECTED: &'static str = "a string";
+
+    fn from_value(value: &'c ValueKind<'_>) -> std::result::Result<Self, Mismatch> {
+        match value {
+            ValueKind::String(string) => Ok(string),
+            _ => Err(Mismatch::Type),
+        }
+    }
+}
+
+impl<'c> FromProperty<'c> for String {
+    const EXPECTED: &'static str = "a string";
+
+    fn from_value(value: &'c ValueKind<'_>) -> std::result::Result<Self, Mismatch> {
+        <&str>::from_value(value).map(str::to_string)
+    }
+}
+
+impl<'c> FromProperty<'c> for Color {
+    const EXPECTED: &'static str = "a color";
+
+    fn from_value(value: &'c ValueKind<'_>) -> std::result::Result<Self, Mismatch> {
+        match *value {
+            ValueKind::Color(color) => Ok(color),
+            _ => Err(Mismatch::Type),
+        }
+    }
+}
+
+impl<'c, T: FromProperty<'c>> FromProperty<'c> for Vec<T> {
+    const EXPECTED: &'static str = "a list";
+
+    fn from_value(value: &'c ValueKind<'_>) -> std::result::Result<Self, Mismatch> {
+        match value {
+            ValueKind::List(list) => list
+                .values
+                .iter()
+                .map(|value| T::from_value(&value.kind))
+                .collect(),
+            _ => Err(Mismatch::Type),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParsedConfig;
+    use crate::ast::Color;
+    use crate::de::Error;
+
+    #[test]
+    fn getters() {
+        let source = "item_type \"Sword\" {\n  cost = 99999999999\n  color = #FF8000\n  effect { power = 2 }\n}";
+        let config = ParsedConfig::parse(source).unwrap();
+
+        assert_eq!(config.get::<i64>("item_type.cost").unwrap(), 99_999_999_999);
+        assert!(matches!(
+            config.get_int_property("item_type.cost"),
+            Err(Error::InvalidValue { range, .. }) if range == Some(29..40)
+        ));
+        assert!(matches!(
+            config.get_int_property("item_type.color"),
+            Err(Error::InvalidType { .. })
+        ));
+        assert!(!config.get_bool_property("item_type.stackable").unwrap());
+        assert!(!config
+            .get_bool_property("item_type#Sword.stackable")
+            .unwrap());
+        for path in ["item_type#Bow.stackable", "itme_type.stackable"] {
+            assert!(matches!(
+                config.get_bool_property(path),
+                Err(Error::PathNotFound { path: found }) if found == path
+            ));
+        }
+        assert_eq!(
+            config.get_color_property("item_type#Sword.color").unwrap(),
+            Color {
+                r: 255,
+                g: 128,
+                b: 0
+            }
+        );
+        assert_eq!(
+            config.get_float_property("item_type.effect.power").unwrap(),
+            2.0
+        );
+        assert!(matches!(
+            config.get_float_property("item_type#Bow.cost"),
+            Err(Error::PathNotFound { path }) if path == "item_type#Bow.cost"
+        ));
+        assert_eq!(
+            config.get_or("item_type.name", "Unnamed").unwrap(),
+            "Unnamed"
+        );
+        assert!(config.get_list_property("item_type.color").is_err());
+    }
+}
@@ -0,0 +1,751 @@
+use crate::ast::{doc_text, Flag, Item, StructBlock, Value, ValueKind};
+use crate::de::{
+    suggestions, DocumentedAccess, DynamicAccess, DynamicSource, Error, Options, Result,
+    SpannedAccess, DOCUMENTED, DYNAMIC, REF_FIELDS, SPANNED,
+};
+use crate::lexer::Span;
+use serde::de::{self, IntoDeserializer, Unexpected, Visitor};
+use serde::forward_to_deserialize_any;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+
+/// Deserializes a run of struct blocks of the same type, either as a sequence or, for a single
+/// struct, as just the first one. `consumed` keeps track of how many were used.
+pub struct StructsDeserializer<'a, 'de> {
+    blocks: Vec<&'a StructBlock<'de>>,
+    source: &'de str,
+    options: &'a Options,
+    consumed: &'a Cell<usize>,
+}
+
+impl<'a, 'de> StructsDeserializer<'a, 'de> {
+    pub fn new(
+        blocks: Vec<&'a StructBlock<'de>>,
+        source: &'de str,
+        options: &'a Options,
+        consumed: &'a Cell<usize>,
+    ) -> Self {
+        Self {
+            blocks,
+            source,
+            options,
+            consumed,
+        }
+    }
+}
+
+impl<'de: 'a, 'a> de::Deserializer<'de> for StructsDeserializer<'a, 'de> {
+    type Error = Error;
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct tuple tuple_struct map enum identifier
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(
+            Unexpected::Other("struct block"),
+            &visitor,
+        ))
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(StructsSeqAccess {
+            blocks: self.blocks,
+            source: self.source,
+            options: self.options,
+            consumed: self.consumed,
+        })
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if name == SPANNED {
+            let span = match self.blocks.first() {
+                Some(block) => block.span.clone(),
+                None => 0..0,
+            };
+            return visitor.visit_map(SpannedAccess::new(self.source, span, self));
+        }
+
+        if name == DOCUMENTED {
+            let doc = self.blocks.first().and_then(|block| block.doc());
+            return visitor.visit_map(DocumentedAccess::new(doc, self));
+        }
+
+        match self.blocks.first() {
+            Some(block) => {
+                self.consumed.set(1);
+                let result = StructBlockDeserializer::new(block, self.source, self.options)
+                    .deserialize_struct(name, fields, visitor);
+                self.options.blame(&block.span, result)
+            }
+            None => Err(de::Error::invalid_length(0, &visitor)),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let type_names = &self.options.type_names;
+        let count = match self.blocks.first() {
+            Some(first) => self
+                .blocks
+                .iter()
+                .take_while(|block| type_names.same_type(first.type_name, block.type_name))
+                .count(),
+            None => 0,
+        };
+        self.consumed.set(count);
+        visitor.visit_unit()
+    }
+}
+
+struct StructsSeqAccess<'a, 'de> {
+    blocks: Vec<&'a StructBlock<'de>>,
+    source: &'de str,
+    options: &'a Options,
+    consumed: &'a Cell<usize>,
+}
+
+impl<'de: 'a, 'a> de::SeqAccess<'de> for StructsSeqAccess<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let index = self.consumed.get();
+        let block = match self.blocks.get(index) {
+            Some(block) => block,
+            None => return Ok(None),
+        };
+
+        if !self
+            .options
+            .type_names
+            .same_type(self.blocks[0].type_name, block.type_name)
+        {
+            return Ok(None);
+        }
+
+        self.consumed.set(index + 1);
+        let result = seed.deserialize(StructBlockDeserializer::new(
+            block,
+            self.source,
+            self.options,
+        ));
+        self.options.blame(&block.span, result).map(Some)
+    }
+}
+
+/// Deserializes a single struct block.
+pub struct StructBlockDeserializer<'a, 'de> {
+    block: &'a StructBlock<'de>,
+    source: &'de str,
+    options: &'a Options,
+}
+
+impl<'a, 'de> StructBlockDeserializer<'a, 'de> {
+    pub fn new(block: &'a StructBlock<'de>, source: &'de str, options: &'a Options) -> Self {
+        Self {
+            block,
+            source,
+            options,
+        }
+    }
+
+    /// The location of the type name of the struct block.
+    pub fn type_name_span(&self) -> Span {
+        self.block.type_name_span.clone()
+    }
+}
+
+impl<'de: 'a, 'a> de::Deserializer<'de> for StructBlockDeserializer<'a, 'de> {
+    type Error = Error;
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct tuple tuple_struct map enum identifier seq
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(
+            Unexpected::Other("struct block"),
+            &visitor,
+        ))
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        type_name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if type_name == SPANNED {
+            let span = self.block.span.clone();
+            return visitor.visit_map(SpannedAccess::new(self.source, span, self));
+        }
+
+        if type_name == DOCUMENTED {
+            return visitor.visit_map(DocumentedAccess::new(self.block.doc(), self));
+        }
+
+        if type_name == DYNAMIC {
+            let type_name = self.block.type_name;
+            return visitor.visit_map(DynamicAccess::new(type_name, DynamicSource::Tree(self)));
+        }
+
+        if !fields.contains(&"instance_name") {
+            return Err(Error::MissingInstanceName);
+        }
+
+        let type_names = &self.options.type_names;
+        if !type_names.matches(self.block.type_name, type_name) {
+            return Err(Error::UnexpectedStruct {
+                name: self.block.type_name.to_string(),
+                expected: type_name.to_string(),
+                did_you_mean: suggestions::closest(
+                    self.block.type_name,
+                    type_names.names_for(type_name),
+                ),
+                range: Some(self.block.type_name_span.clone()),
+            });
+        }
+
+        if let Some(instance_name) = self.block.instance_name {
+            self.options
+                .references
+                .add_instance(type_name, instance_name);
+        }
+
+        visitor
+            .visit_map(StructBlockMapAccess::new(
+                self.block,
+                self.source,
+                fields,
+                self.options,
+            ))
+            .map_err(|error| {
+                error
+                    .at(&self.block.type_name_span)
+                    .inherited_by(self.block)
+            })
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+}
+
+struct StructBlockMapAccess<'a, 'de> {
+    block: &'a StructBlock<'de>,
+    source: &'de str,
+    fields: &'static [&'static str],
+    options: &'a Options,
+    instance_name: bool,
+    index: usize,
+    skipped: HashSet<usize>,
+    seen: HashMap<&'de str, &'a Span>,
+}
+
+impl<'a, 'de> StructBlockMapAccess<'a, 'de> {
+    fn new(
+        block: &'a StructBlock<'de>,
+        source: &'de str,
+        fields: &'static [&'static str],
+        options: &'a Options,
+    ) -> Self {
+        let mut skipped = HashSet::new();
+        if options.last_property_wins {
+            let mut last = HashMap::new();
+            for (index, item) in block.items.iter().enumerate() {
+                if let Some((name, _)) = property_name(item) {
+                    if let Some(previous) = last.insert(name, index) {
+                        skipped.insert(previous);
+                    }
+                }
+            }
+        }
+
+        Self {
+            block,
+            source,
+            fields,
+            options,
+            instance_name: true,
+            index: 0,
+            skipped,
+            seen: HashMap::new(),
+        }
+    }
+}
+
+/// The name and location of the name of a property or flag.
+fn property_name<'a, 'de>(item: &'a Item<'de>) -> Option<(&'de str, &'a Span)> {
+    match item {
+        Item::Property(property) => Some((property.name, &property.name_span)),
+        Item::Flag(flag) => Some((flag.name, &flag.span)),
+        Item::Struct(_) => None,
+    }
+}
+
+impl<'de: 'a, 'a> de::MapAccess<'de> for StructBlockMapAccess<'a, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        if self.instance_name {
+            return seed
+                .deserialize("instance_name".into_deserializer())
+                .map(Some);
+        }
+
+        while self.skipped.contains(&self.index) {
+            self.index += 1;
+        }
+
+        let item = match self.block.items.get(self.index) {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+
+        let (name, span) = match item {
+            Item::Struct(block) => {
+                let field = self
+                    .options
+                    .type_names
+                    .find_field(block.type_name, self.fields)
+                    .unwrap_or(block.type_name);
+                let result = seed
+                    .deserialize(field.into_deserializer())
+                    .map(Some)
+                    .map_err(|error| Error::at(error, &block.type_name_span));
+                return self.options.blame(&block.span, result);
+            }
+            Item::Property(property) => (property.name, &property.name_span),
+            Item::Flag(flag) => (flag.name, &flag.span),
+        };
+
+        let result = match self.seen.insert(name, span) {
+            Some(first) => Err(Error::DuplicateProperty {
+                name: name.to_string(),
+                first: first.clone(),
+                second: span.clone(),
+            }),
+            None => seed
+                .deserialize(name.into_deserializer())
+                .map(Some)
+                .map_err(|error| Error::at(error, span)),
+        };
+        self.options.blame(item.span(), result)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        if self.instance_name {
+            self.instance_name = false;
+            let instance_name = self.block.instance_name.unwrap_or("");
+            return seed.deserialize(de::value::BorrowedStrDeserializer::new(instance_name));
+        }
+
+        let items = self.block.items.get(self.index..).unwrap_or_default();
+        match items.first() {
+            None => Err(de::Error::custom("value requested without a key")),
+            Some(Item::Property(property)) => {
+                self.index += 1;
+                let result = seed
+                    .deserialize(
+                        ValueDeserializer::new(&property.value, self.source)
+                            .comments(&property.comments)
+                            .options(self.options),
+                    )
+                    .map_err(|error| error.at(&property.value.span));
+                self.options.blame(&property.span, result)
+            }
+            Some(Item::Flag(flag)) => {
+                self.index += 1;
+                let result = seed
+                    .deserialize(FlagDeserializer::new(flag, self.source))
+                    .map_err(|error| error.at(&flag.span));
+                self.options.blame(&flag.span, result)
+            }
+            Some(Item::Struct(first)) => {
+                let type_names = &self.options.type_names;
+                let blocks = items
+                    .iter()
+                    .map_while(|item| match item {
+                        Item::Struct(block)
+                            if type_names.same_type(first.type_name, block.type_name) =>
+                        {
+                            Some(block)
+                        }
+                        _ => None,
+                    })
+                    .collect();
+
+                let consumed = Cell::new(0);
+                let result = seed
+                    .deserialize(StructsDeserializer::new(
+                        blocks,
+                        self.source,
+                        self.options,
+                        &consumed,
+                    ))
+                    .map_err(|error| error.at(&first.span));
+                self.index += consumed.get().max(1);
+                self.options.blame(&first.span, result)
+            }
+        }
+    }
+}
+
+/// Deserializes a flag, which is always `true`.
+pub struct FlagDeserializer<'a, 'de> {
+    flag: &'a Flag<'de>,
+    source: &'de str,
+}
+
+impl<'a, 'de> FlagDeserializer<'a, 'de> {
+    pub fn new(flag: &'a Flag<'de>, source: &'de str) -> Self {
+        Self { flag, source }
+    }
+}
+
+impl<'de: 'a, 'a> de::Deserializer<'de> for FlagDeserializer<'a, 'de> {
+    type Error = Error;
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple tuple_struct map enum
+        identifier
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if name == SPANNED {
+            let span = self.flag.span.clone();
+            return visitor.visit_map(SpannedAccess::new(self.source, span, self));
+        }
+
+        if name == DOCUMENTED {
+            return visitor.visit_map(DocumentedAccess::new(self.flag.doc(), self));
+        }
+
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_bool(true)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+}
+
+/// Deserializes the value of a property or list element.
+pub struct ValueDeserializer<'a, 'de> {
+    value: &'a Value<'de>,
+    source: &'de str,
+    comments: &'a [&'de str],
+    options: Option<&'a Options>,
+}
+
+impl<'a, 'de> ValueDeserializer<'a, 'de> {
+    pub fn new(value: &'a Value<'de>, source: &'de str) -> Self {
+        Self {
+            value,
+            source,
+            comments: &[],
+            options: None,
+        }
+    }
+
+    /// The settings of the deserializer, for custom types and references.
+    pub fn options(mut self, options: &'a Options) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    /// Parses the value as the custom type `type_name`, if there is a parser for it.
+    fn custom_value(&self, type_name: &str) -> Option<Result<Value<'static>>> {
+        let raw = &self.source[self.value.span.clone()];
+        self.options?
+            .custom_types
+            .parse(type_name, raw, &self.value.span)
+    }
+
+    /// The doc comments of the property the value belongs to.
+    pub fn comments(mut self, comments: &'a [&'de str]) -> Self {
+        self.comments = comments;
+        self
+    }
+}
+
+impl<'de: 'a, 'a> de::Deserializer<'de> for ValueDeserializer<'a, 'de> {
+    type Error = Error;
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 string
+        bytes byte_buf unit unit_struct seq tuple map identifier
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if fields == REF_FIELDS {
+            let span = self.value.span.clone();
+            let value = ValueDeserializer::new(self.value, self.source);
+            let target = <String as de::Deserialize>::deserialize(value)?;
+            if let Some(options) = self.options {
+                options.references.add_reference(name, &target, &span);
+            }
+            let target = target.into_deserializer();
+            return visitor.visit_map(SpannedAccess::new(self.source, span, target));
+        }
+
+        if let Some(value) = self.custom_value(name) {
+            return ValueDeserializer::new(&value?, self.source)
+                .deserialize_struct(name, fields, visitor);
+        }
+
+        if name == SPANNED {
+            let span = self.value.span.clone();
+            return visitor.visit_map(SpannedAccess::new(self.source, span, self));
+        }
+
+        if name == DOCUMENTED {
+            return visitor.visit_map(DocumentedAccess::new(doc_text(self.comments), self));
+        }
+
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match &self.value.kind {
+            ValueKind::String(Cow::Borrowed(value)) => visitor.visit_borrowed_str(value),
+            ValueKind::String(Cow::Owned(value)) => visitor.visit_str(value),
+            ValueKind::Char(value) => visitor.visit_char(*value),
+            ValueKind::Integer(value) => visitor.visit_i64(*value),
+            ValueKind::Float(value) => visitor.visit_f64(*value),
+            ValueKind::Color(color) => {
+                let components = vec![color.r, color.g, color.b];
+                visitor.visit_seq(de::value::SeqDeserializer::new(components.into_iter()))
+            }
+            ValueKind::List(list) => visitor.visit_seq(ListSeqAccess {
+                values: list.values.iter(),
+                source: self.source,
+                options: self.options,
+            }),
+        }
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value.kind {
+            ValueKind::Integer(value) if (0..=255).contains(&value) => {
+                visitor.visit_char(value as u8 as char)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match &self.value.kind {
+            ValueKind::String(Cow::Owned(value)) => Err(Error::MultiLineStringOnBorrowedStr {
+                value: value.clone(),
+                range: self.value.span.clone(),
+            }),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if let Some(value) = self.custom_value(name) {
+            return ValueDeserializer::new(&value?, self.source)
+                .deserialize_newtype_struct(name, visitor);
+        }
+
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if let Some(value) = self.custom_value(name) {
+            return ValueDeserializer::new(&value?, self.source)
+                .deserialize_tuple_struct(name, len, visitor);
+        }
+
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if let Some(value) = self.custom_value(name) {
+            return ValueDeserializer::new(&value?, self.source)
+                .deserialize_enum(name, variants, visitor);
+        }
+
+        match &self.value.kind {
+            ValueKind::String(Cow::Borrowed(value)) => {
+                visitor.visit_enum(de::value::BorrowedStrDeserializer::new(value))
+            }
+            ValueKind::String(Cow::Owned(value)) => {
+                visitor.visit_enum(de::value::StrDeserializer::new(value))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+}
+
+struct ListSeqAccess<'a, 'de> {
+    values: std::slice::Iter<'a, Value<'de>>,
+    source: &'de str,
+    options: Option<&'a Options>,
+}
+
+impl<'de: 'a, 'a> de::SeqAccess<'de> for ListSeqAccess<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.values.next() {
+            Some(value) => {
+                let mut de = ValueDeserializer::new(value, self.source);
+                de.options = self.options;
+                seed.deserialize(de)
+                    .map(Some)
+                    .map_err(|error| error.at(&value.span))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
@@ -0,0 +1,124 @@
+use crate::ast::{Value, ValueKind};
+use crate::de::{Error, Result};
+use crate::lexer::Span;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Parses the raw text of a value of a custom type.
+type Parser = Box<dyn Fn(&str) -> std::result::Result<ValueKind<'static>, String> + Send>;
+
+/// The parsers registered with [`Deserializer::custom_type`], by the (serde) name of the type
+/// they parse values of.
+///
+/// [`Deserializer::custom_type`]: struct.Deserializer.html#method.custom_type
+#[derive(Default)]
+pub(crate) struct CustomTypes {
+    parsers: HashMap<&'static str, Parser>,
+}
+
+impl CustomTypes {
+    pub(crate) fn add(&mut self, type_name: &'static str, parser: Parser) {
+        self.parsers.insert(type_name, parser);
+    }
+
+    pub(crate) fn contains(&self, type_name: &str) -> bool {
+        self.parsers.contains_key(type_name)
+    }
+
+    /// Parses `raw`, found at `span`, as a value of the custom type `type_name`. Returns `None` if
+    /// there is no parser for the type.
+    pub(crate) fn parse(
+        &self,
+        type_name: &str,
+        raw: &str,
+        span: &Span,
+    ) -> Option<Result<Value<'static>>> {
+        let parser = self.parsers.get(type_name)?;
+        Some(match parser(raw) {
+            Ok(kind) => {
+                let mut value = Value {
+                    kind,
+                    span: span.clone(),
+                };
+                relocate(&mut value, span);
+                Ok(value)
+            }
+            Err(expected) => Err(Error::InvalidValue {
+                unexpected: format!("`{}`", raw),
+                expected,
+                range: Some(span.clone()),
+            }),
+        })
+    }
+}
+
+/// Sets the location of `value` and the values within it to `span`.
+fn relocate(value: &mut Value<'_>, span: &Span) {
+    value.span = span.clone();
+    if let ValueKind::List(list) = &mut value.kind {
+        list.span = span.clone();
+        for value in &mut list.values {
+            relocate(value, span);
+        }
+    }
+}
+
+impl fmt::Debug for CustomTypes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.parsers.keys()).finish()
+    }
+}
+
+/// Finds the raw text of the value of a custom type starting at `start`, which runs up to the end
+/// of the line, a comment, a `,`, `]` or `}` that is not within quotes, or the start of the next
+/// property, flag or struct block.
+pub(crate) fn raw_value(source: &str, start: usize) -> Span {
+    let mut end = start;
+    let mut quote = None;
+    let mut chars = source[start..].char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        let after_space = source[..start + index].ends_with(char::is_whitespace);
+        match (quote, c) {
+            (None, _) if after_space && item_starts(&source[start + index..]) => break,
+            (Some('\''), '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if q == c => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '\n') | (None, '\r') | (None, ',') | (None, ']') | (None, '}') => break,
+            (None, '/') if matches!(chars.peek(), Some((_, '/')) | Some((_, '*'))) => break,
+            (None, c) if c.is_whitespace() => continue,
+            _ => {}
+        }
+        end = start + index + c.len_utf8();
+    }
+
+    start..end
+}
+
+/// Whether `source` starts with a property, flag or struct block, i.e. an identifier.
+fn item_starts(source: &str) -> bool {
+    source.starts_with(|c: char| c.is_ascii_alphabetic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::raw_value;
+
+    #[test]
+    fn raw_values() {
+        let raw = |source: &'static str| &source[raw_value(source, 0)];
+        assert_eq!(raw("3:4\n  cost = 1"), "3:4");
+        assert_eq!(raw("3 : 4 // comment"), "3 : 4");
+        assert_eq!(raw("3:4, 5:6]"), "3:4");
+        assert_eq!(raw("\"a, b\" }"), "\"a, b\"");
+        assert_eq!(raw("',' ]"), "','");
+        assert_eq!(raw("3:4 sharp }"), "3:4");
+        assert_eq!(raw("3:4 hp = 1 }"), "3:4");
+        assert_eq!(raw("3:4 effect { }"), "3:4");
+        assert_eq!(raw("3:4 item_type \"Sword\" { }"), "3:4");
+        assert_eq!(raw("1d4+x2 }"), "1d4+x2");
+        assert_eq!(raw("\"a\" b = 1"), "\"a\"");
+    }
+}
@@ -0,0 +1,115 @@
+//! Compiler-quality error reports through [`miette`], enabled by the `miette` feature.
+//!
+//! The errors don't contain the source string, so it has to be attached to the report:
+//!
+//! ```
+//! use miette::Report;
+//! use serde_derive::Deserialize;
+//! use serde_tcod_config_parser::de::Deserializer;
+//!
+//! #[derive(Debug, Deserialize)]
+//! #[serde(rename = "monster")]
+//! struct Monster {
+//!     instance_name: String,
+//!     hp: i32,
+//! }
+//!
+//! let source = "monster \"Orc\" { hp = 10 hp = 12 }";
+//! let error = Deserializer::from_str::<Monster>(source).unwrap_err();
+//! let report = format!("{:?}", Report::new(error).with_source_code(source));
+//! assert!(report.contains("TCOD0005"));
+//! assert!(report.contains("first given here"));
+//! ```
+//!
+//! [`miette`]: https://crates.io/crates/miette
+use crate::de::{Error, InvalidCharError};
+use miette::{Diagnostic, LabeledSpan};
+use std::fmt::Display;
+
+impl Diagnostic for Error {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(Error::code(self)))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Error::help(self).map(|help| Box::new(help) as Box<dyn Display>)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let primary = |label: String, range: &std::ops::Range<usize>| {
+            LabeledSpan::new_primary_with_span(Some(label), range.clone())
+        };
+
+        let labels = match self {
+            Error::UnexpectedToken {
+                expected, range, ..
+            } => vec![primary(format!("expected {}", expected), range)],
+            Error::Lexical { error, range } => vec![primary(error.to_string(), range)],
+            Error::InvalidNumber { range, .. } => {
+                vec![primary(
+                    "this number cannot be represented".to_string(),
+                    range,
+                )]
+            }
+            Error::DuplicateProperty { first, second, .. } => vec![
+                primary("given again here".to_string(), second),
+                LabeledSpan::new_with_span(Some("first given here".to_string()), first.clone()),
+            ],
+            Error::MultiLineStringOnBorrowedStr { range, .. } => {
+                vec![primary(
+                    "this string spans several lines".to_string(),
+                    range,
+                )]
+            }
+            Error::Inherited { source, block, .. } => {
+                let mut labels = source.labels()?.collect::<Vec<_>>();
+                labels.push(LabeledSpan::new_with_span(
+                    Some("inheriting struct block".to_string()),
+                    block.clone(),
+                ));
+                labels
+            }
+            _ => vec![LabeledSpan::new_primary_with_span(None, self.range()?)],
+        };
+
+        Some(Box::new(labels.into_iter()))
+    }
+
+    fn diagnostic_source(&self) -> Option<&dyn Diagnostic> {
+        match self {
+            Error::InvalidChar { source } => Some(source),
+            Error::Inherited { source, .. } => source.diagnostic_source(),
+            _ => None,
+        }
+    }
+}
+
+impl Diagnostic for InvalidCharError {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(InvalidCharError::code(self)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::de::{Deserializer, Error};
+    use crate::fixtures::Monster;
+    use miette::Diagnostic;
+
+    #[test]
+    fn duplicate_property_labels() {
+        let error = Deserializer::from_str::<Monster>("monster { hp = 10 hp = 12 }").unwrap_err();
+        assert!(matches!(error, Error::DuplicateProperty { .. }));
+
+        assert_eq!(
+            Diagnostic::code(&error).map(|code| code.to_string()),
+            Some("TCOD0005".to_string())
+        );
+        let labels = error.labels().unwrap().collect::<Vec<_>>();
+        assert_eq!(labels.len(), 2);
+        assert!(labels[0].primary());
+        assert_eq!((labels[0].offset(), labels[0].len()), (18, 2));
+        assert_eq!(labels[1].label(), Some("first given here"));
+        assert!(Diagnostic::help(&error).is_some());
+    }
+}
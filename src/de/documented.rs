@@ -0,0 +1,137 @@
+use crate::de::{Error, Result, DOCUMENTED};
+use serde::de::{self, IntoDeserializer, Visitor};
+use serde_derive::Deserialize;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+const FIELDS: &[&str] = &["doc", "value"];
+
+/// A value along with the comments that immediately precede it in the config file.
+///
+/// Comments are only considered documentation if they are on lines of their own, directly above
+/// the property, flag or struct block, without a blank line in between. Comment markers are
+/// stripped, so
+///
+/// ```text
+/// // The cost, in gold.
+/// cost = 10
+/// ```
+///
+/// gives `value` 10 and `doc` `Some("The cost, in gold.")`.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone)]
+pub struct Documented<T> {
+    /// The value itself.
+    pub value: T,
+    /// The text of the comments preceding the value, if there were any.
+    pub doc: Option<String>,
+}
+
+impl<T> Deref for Documented<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Documented<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum Field {
+    Doc,
+    Value,
+}
+
+impl<'de, T: de::Deserialize<'de>> de::Deserialize<'de> for Documented<T> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct DocumentedVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: de::Deserialize<'de>> Visitor<'de> for DocumentedVisitor<T> {
+            type Value = Documented<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a documented value")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> std::result::Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let mut doc = None;
+                let mut value = None;
+                while let Some(field) = map.next_key()? {
+                    match field {
+                        Field::Doc => doc = Some(map.next_value()?),
+                        Field::Value => value = Some(map.next_value()?),
+                    }
+                }
+
+                Ok(Documented {
+                    value: value.ok_or_else(|| de::Error::missing_field("value"))?,
+                    doc,
+                })
+            }
+        }
+
+        deserializer.deserialize_struct(DOCUMENTED, FIELDS, DocumentedVisitor(PhantomData))
+    }
+}
+
+/// Hands a [`Documented`] its documentation, followed by its value, which is deserialized by
+/// `de`.
+///
+/// [`Documented`]: struct.Documented.html
+pub(crate) struct DocumentedAccess<D> {
+    doc: Option<String>,
+    de: Option<D>,
+}
+
+impl<D> DocumentedAccess<D> {
+    pub(crate) fn new(doc: Option<String>, de: D) -> Self {
+        Self { doc, de: Some(de) }
+    }
+}
+
+impl<'de, D> de::MapAccess<'de> for DocumentedAccess<D>
+where
+    D: de::Deserializer<'de, Error = Error>,
+{
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        let field = if self.doc.is_some() {
+            "doc"
+        } else if self.de.is_some() {
+            "value"
+        } else {
+            return Ok(None);
+        };
+        seed.deserialize(field.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        if let Some(doc) = self.doc.take() {
+            return seed.deserialize(doc.into_deserializer());
+        }
+
+        match self.de.take() {
+            Some(de) => seed.deserialize(de),
+            None => Err(de::Error::custom("value requested twice")),
+        }
+    }
+}
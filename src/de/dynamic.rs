@@ -0,0 +1,411 @@
+use crate::de::{suggestions, Deserializer, Error, Result, StructBlockDeserializer, DYNAMIC};
+use crate::lexer::Span;
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde_derive::Deserialize;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::sync::Arc;
+
+const FIELDS: &[&str] = &["type_name", "struct_block"];
+
+type Constructor<T> = Arc<dyn Fn(DynamicSource<'_, '_>) -> Result<Box<T>> + Send + Sync>;
+
+/// Deserializes the registered type for a struct block from the deserializer at it.
+type Construct = Box<dyn FnOnce(DynamicSource<'_, '_>) -> Result<()>>;
+
+thread_local! {
+    /// What the [`RegistryVisitor`] asks the [`DynamicAccess`] it visits to do with its struct
+    /// block, as serde has no way to hand the deserializer over to the visitor.
+    static PENDING: RefCell<Option<Construct>> = const { RefCell::new(None) };
+}
+
+/// The Rust types that struct blocks are deserialized as when the type to deserialize is a trait
+/// object `T`, e.g. `Box<dyn Component>`, keyed by struct type name. This makes it possible to
+/// fill a `Vec<Box<dyn Component>>` from whatever struct blocks appear, like [typetag] does for
+/// other formats.
+///
+/// `Box<dyn Component>` is made deserializable by implementing `Deserialize` for it with
+/// [`deserialize`], using a registry that is typically kept in a static. To let the struct blocks
+/// of all the registered types go into the same field, tell the [`Deserializer`] about it with
+/// [`registry_field`]. The blocks must then be given one after another.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_derive::Deserialize;
+/// use serde_tcod_config_parser::de::{Deserializer, Registry};
+/// use std::sync::OnceLock;
+///
+/// trait Component {
+///     fn describe(&self) -> String;
+/// }
+///
+/// #[derive(Deserialize)]
+/// #[serde(rename = "ai")]
+/// struct Ai {
+///     instance_name: String,
+/// }
+///
+/// impl Component for Ai {
+///     fn describe(&self) -> String {
+///         format!("{} ai", self.instance_name)
+///     }
+/// }
+///
+/// #[derive(Deserialize)]
+/// #[serde(rename = "inventory")]
+/// struct Inventory {
+///     instance_name: String,
+///     slots: u32,
+/// }
+///
+/// impl Component for Inventory {
+///     fn describe(&self) -> String {
+///         format!("{} slots", self.slots)
+///     }
+/// }
+///
+/// fn components() -> &'static Registry<dyn Component> {
+///     static REGISTRY: OnceLock<Registry<dyn Component>> = OnceLock::new();
+///     REGISTRY.get_or_init(|| {
+///         Registry::<dyn Component>::new()
+///             .register("ai", |ai: Ai| Box::new(ai))
+///             .register("inventory", |inventory: Inventory| Box::new(inventory))
+///     })
+/// }
+///
+/// impl<'de> Deserialize<'de> for Box<dyn Component> {
+///     fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+///         components().deserialize(deserializer)
+///     }
+/// }
+///
+/// #[derive(Deserialize)]
+/// #[serde(rename = "monster")]
+/// struct Monster {
+///     instance_name: String,
+///     components: Vec<Box<dyn Component>>,
+/// }
+///
+/// let source = r#"monster "Orc" { ai "melee" { } inventory { slots = 4 } }"#;
+/// let mut de = Deserializer::new(source).registry_field("components", components());
+/// let orc = Monster::deserialize(&mut de).unwrap();
+/// let descriptions = orc.components.iter().map(|c| c.describe()).collect::<Vec<_>>();
+/// assert_eq!(descriptions, ["melee ai", "4 slots"]);
+/// ```
+///
+/// The registered types are deserialized by the same deserializer as the rest of the file, with
+/// the same settings.
+///
+/// [typetag]: https://crates.io/crates/typetag
+/// [`deserialize`]: #method.deserialize
+/// [`Deserializer`]: struct.Deserializer.html
+/// [`registry_field`]: struct.Deserializer.html#method.registry_field
+pub struct Registry<T: ?Sized> {
+    constructors: Vec<(&'static str, Constructor<T>)>,
+}
+
+impl<T: ?Sized + 'static> Registry<T> {
+    /// Creates a registry without any types.
+    pub fn new() -> Self {
+        Self {
+            constructors: Vec::new(),
+        }
+    }
+
+    /// Deserializes struct blocks of the type `type_name` as `C`, which is then turned into a
+    /// `Box<T>` with `into`. `type_name` should be the (serde) name of `C`.
+    pub fn register<C>(mut self, type_name: &'static str, into: fn(C) -> Box<T>) -> Self
+    where
+        C: DeserializeOwned + 'static,
+    {
+        let constructor = move |source: DynamicSource<'_, '_>| C::deserialize(source).map(into);
+        self.constructors.push((type_name, Arc::new(constructor)));
+        self
+    }
+
+    /// The struct type names of all the registered types.
+    pub fn type_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.constructors.iter().map(|(type_name, _)| *type_name)
+    }
+
+    /// Deserializes a struct block as the registered type for its struct type name, which is an
+    /// [`Error::UnknownStruct`] if there is none. Only works with the deserializers of this crate.
+    ///
+    /// [`Error::UnknownStruct`]: enum.Error.html#variant.UnknownStruct
+    pub fn deserialize<'de, D>(&self, deserializer: D) -> std::result::Result<Box<T>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_struct(DYNAMIC, FIELDS, RegistryVisitor(self))
+    }
+
+    /// What to do with a struct block whose type name is `type_name`: deserialize it as the
+    /// registered type and put it into `slot`.
+    fn construct(&self, type_name: &str, slot: Rc<RefCell<Option<Box<T>>>>) -> Construct {
+        match self
+            .constructors
+            .iter()
+            .find(|(name, _)| *name == type_name)
+        {
+            Some((_, constructor)) => {
+                let constructor = Arc::clone(constructor);
+                Box::new(move |source: DynamicSource<'_, '_>| {
+                    *slot.borrow_mut() = Some(constructor(source)?);
+                    Ok(())
+                })
+            }
+            None => {
+                let name = type_name.to_string();
+                let did_you_mean = suggestions::closest(type_name, self.type_names());
+                Box::new(move |source: DynamicSource<'_, '_>| {
+                    Err(Error::UnknownStruct {
+                        name,
+                        did_you_mean,
+                        range: source.type_name_span(),
+                    })
+                })
+            }
+        }
+    }
+}
+
+impl<T: ?Sized + 'static> Default for Registry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for Registry<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let type_names = self.constructors.iter().map(|(type_name, _)| type_name);
+        f.debug_tuple("Registry")
+            .field(&type_names.collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "snake_case")]
+enum Field {
+    TypeName,
+    StructBlock,
+}
+
+struct RegistryVisitor<'r, T: ?Sized>(&'r Registry<T>);
+
+impl<'de, 'r, T: ?Sized + 'static> Visitor<'de> for RegistryVisitor<'r, T> {
+    type Value = Box<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a struct block")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> std::result::Result<Self::Value, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        let type_name = match map.next_key()? {
+            Some(Field::TypeName) => map.next_value::<&'de str>()?,
+            _ => return Err(de::Error::missing_field("type_name")),
+        };
+
+        let slot = Rc::new(RefCell::new(None));
+        let construct = self.0.construct(type_name, Rc::clone(&slot));
+        PENDING.with(|pending| *pending.borrow_mut() = Some(construct));
+        match map.next_key()? {
+            Some(Field::StructBlock) => map.next_value::<de::IgnoredAny>()?,
+            _ => return Err(de::Error::missing_field("struct_block")),
+        };
+
+        let value = slot.borrow_mut().take();
+        value.ok_or_else(|| de::Error::custom("the struct block was not deserialized"))
+    }
+}
+
+/// The deserializer at a struct block that a [`Registry`] deserializes as a registered type.
+///
+/// [`Registry`]: struct.Registry.html
+pub(crate) enum DynamicSource<'a, 'de> {
+    Streaming(&'a mut Deserializer<'de>),
+    Tree(StructBlockDeserializer<'a, 'de>),
+}
+
+impl DynamicSource<'_, '_> {
+    /// The location of the type name of the struct block.
+    fn type_name_span(&self) -> Span {
+        match self {
+            DynamicSource::Streaming(de) => de.lexer.range(),
+            DynamicSource::Tree(de) => de.type_name_span(),
+        }
+    }
+}
+
+macro_rules! forward_to_source {
+    ($($method: ident($($arg: ident: $ty: ty),*))*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                match self {
+                    DynamicSource::Streaming(de) => de.$method($($arg,)* visitor),
+                    DynamicSource::Tree(de) => de.$method($($arg,)* visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de: 'a, 'a> de::Deserializer<'de> for DynamicSource<'a, 'de> {
+    type Error = Error;
+
+    forward_to_source! {
+        deserialize_any()
+        deserialize_bool()
+        deserialize_i8()
+        deserialize_i16()
+        deserialize_i32()
+        deserialize_i64()
+        deserialize_i128()
+        deserialize_u8()
+        deserialize_u16()
+        deserialize_u32()
+        deserialize_u64()
+        deserialize_u128()
+        deserialize_f32()
+        deserialize_f64()
+        deserialize_char()
+        deserialize_str()
+        deserialize_string()
+        deserialize_bytes()
+        deserialize_byte_buf()
+        deserialize_option()
+        deserialize_unit()
+        deserialize_unit_struct(name: &'static str)
+        deserialize_newtype_struct(name: &'static str)
+        deserialize_seq()
+        deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_map()
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+        deserialize_identifier()
+        deserialize_ignored_any()
+    }
+}
+
+/// Hands a [`Registry`] the type name of a struct block, and then deserializes the struct block
+/// as the type it picked.
+///
+/// [`Registry`]: struct.Registry.html
+pub(crate) struct DynamicAccess<'a, 'de> {
+    type_name: &'de str,
+    source: Option<DynamicSource<'a, 'de>>,
+    index: usize,
+}
+
+impl<'a, 'de> DynamicAccess<'a, 'de> {
+    pub(crate) fn new(type_name: &'de str, source: DynamicSource<'a, 'de>) -> Self {
+        Self {
+            type_name,
+            source: Some(source),
+            index: 0,
+        }
+    }
+}
+
+impl<'de: 'a, 'a> de::MapAccess<'de> for DynamicAccess<'a, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match FIELDS.get(self.index) {
+            Some(field) => seed.deserialize(field.into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let index = self.index;
+        self.index += 1;
+        match index {
+            0 => seed.deserialize(de::value::BorrowedStrDeserializer::new(self.type_name)),
+            1 => {
+                let construct = PENDING.with(|pending| pending.borrow_mut().take());
+                match (construct, self.source.take()) {
+                    (Some(construct), Some(source)) => construct(source)?,
+                    _ => {
+                        return Err(de::Error::custom(
+                            "struct block requested without a registry",
+                        ))
+                    }
+                }
+                seed.deserialize(().into_deserializer())
+            }
+            _ => Err(de::Error::custom("value requested twice")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast;
+    use crate::de::{Deserializer, Error};
+    use crate::fixtures::{registry, ItemType};
+    use serde::Deserialize;
+
+    #[test]
+    fn registered_types() {
+        let source = "item_type { effect { power = 2 } effect { power = 3 } cost = 1 }";
+        let mut de = Deserializer::new(source).registry_field("components", registry());
+        let item = ItemType::deserialize(&mut de).unwrap();
+        assert_eq!(item.cost, 1);
+        assert_eq!(item.components.iter().map(|c| c.power()).sum::<i32>(), 5);
+
+        let mut de = Deserializer::from_file(ast::parse(source).unwrap())
+            .registry_field("components", registry());
+        assert_eq!(ItemType::deserialize(&mut de).unwrap().components.len(), 2);
+
+        let source = "item_type { cost = 1 effect { power = \"2\" } }";
+        let mut de = Deserializer::new(source).registry_field("components", registry());
+        let error = ItemType::deserialize(&mut de).err().unwrap();
+        assert_eq!(error.range(), Some(38..41));
+
+        let source = "item_type { cost = 1 efect { } }";
+        let mut de = Deserializer::new(source).type_name_alias("components", "efect");
+        assert!(matches!(
+            ItemType::deserialize(&mut de),
+            Err(Error::UnknownStruct { did_you_mean, range, .. })
+                if did_you_mean == ["effect"] && range == (21..26)
+        ));
+    }
+
+    #[test]
+    fn caller_settings() {
+        let source = r#"item_type "Bow" {
+  cost = 1
+  effect { power = 2 power = 3 }
+  ammo { item = "Arow" }
+}"#;
+        let check = |de: Deserializer| {
+            let mut de = de
+                .registry_field("components", registry())
+                .last_property_wins(true);
+            let item = ItemType::deserialize(&mut de).unwrap();
+            assert_eq!(item.components[0].power(), 3);
+            assert!(matches!(
+                de.check_references().unwrap_err(),
+                Error::DanglingReference { name, .. } if name == "Arow"
+            ));
+        };
+
+        check(Deserializer::new(source));
+        check(Deserializer::from_file(ast::parse(source).unwrap()));
+    }
+}
@@ -0,0 +1,95 @@
+/// The kind of an [`Error`], for matching on errors without caring about their details.
+///
+/// [`Error`]: enum.Error.html
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A free-form error, typically from a `Deserialize` implementation.
+    Custom,
+    /// A token that was unexpected was encountered.
+    UnexpectedToken,
+    /// The source string could not be tokenized.
+    Lexical,
+    /// A number could not be represented.
+    InvalidNumber,
+    /// The same property was given more than once within a single struct block.
+    DuplicateProperty,
+    /// A different struct than was expected was encountered.
+    UnexpectedStruct,
+    /// A field that does not exist on the type being deserialized was encountered.
+    UnknownField,
+    /// A field that the type being deserialized requires was not given.
+    MissingField,
+    /// A value of the wrong type was given, e.g. a string for an integer field.
+    InvalidType,
+    /// A value of the right type, but with an unacceptable value was given.
+    InvalidValue,
+    /// A list or a run of struct blocks had the wrong number of elements.
+    InvalidLength,
+    /// An enum variant that does not exist was given.
+    UnknownVariant,
+    /// The same field was deserialized more than once.
+    DuplicateField,
+    /// A struct block whose type is not declared in a schema or registered was encountered.
+    UnknownStruct,
+    /// A property or flag that is not declared in a schema was encountered.
+    UnknownProperty,
+    /// A property that a schema declares mandatory was not given.
+    MissingProperty,
+    /// A reference points at a struct block that doesn't exist.
+    DanglingReference,
+    /// A struct block inherits from itself, directly or indirectly.
+    InheritanceCycle,
+    /// A file named by an `include` directive could not be loaded.
+    Include,
+    /// A file includes itself, directly or indirectly.
+    IncludeCycle,
+    /// Struct blocks and lists are nested too deeply.
+    NestingTooDeep,
+    /// A struct being deserialized has no `instance_name` field.
+    MissingInstanceName,
+    /// An invalid `char` representation was encountered.
+    InvalidChar,
+    /// Nothing exists at a given path.
+    PathNotFound,
+    /// A value cannot be written in a libtcod config file.
+    UnrepresentableValue,
+    /// A multi-line string was given for a borrowed `str` field.
+    MultiLineStringOnBorrowedStr,
+}
+
+impl ErrorKind {
+    /// A stable code identifying the kind of error, e.g. `TCOD0003`. Codes are never reused for a
+    /// different kind of error, so they are suitable for looking up documentation or for
+    /// filtering diagnostics.
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorKind::Custom => "TCOD0001",
+            ErrorKind::UnexpectedToken => "TCOD0002",
+            ErrorKind::Lexical => "TCOD0003",
+            ErrorKind::InvalidNumber => "TCOD0004",
+            ErrorKind::DuplicateProperty => "TCOD0005",
+            ErrorKind::UnexpectedStruct => "TCOD0006",
+            ErrorKind::UnknownField => "TCOD0007",
+            ErrorKind::MissingField => "TCOD0008",
+            ErrorKind::MissingInstanceName => "TCOD0009",
+            ErrorKind::InvalidChar => "TCOD0010",
+            ErrorKind::PathNotFound => "TCOD0011",
+            ErrorKind::UnrepresentableValue => "TCOD0012",
+            ErrorKind::MultiLineStringOnBorrowedStr => "TCOD0013",
+            ErrorKind::InvalidType => "TCOD0014",
+            ErrorKind::InvalidValue => "TCOD0015",
+            ErrorKind::InvalidLength => "TCOD0016",
+            ErrorKind::UnknownVariant => "TCOD0017",
+            ErrorKind::DuplicateField => "TCOD0018",
+            ErrorKind::UnknownStruct => "TCOD0019",
+            ErrorKind::UnknownProperty => "TCOD0020",
+            ErrorKind::MissingProperty => "TCOD0021",
+            ErrorKind::DanglingReference => "TCOD0022",
+            ErrorKind::InheritanceCycle => "TCOD0023",
+            ErrorKind::Include => "TCOD0024",
+            ErrorKind::IncludeCycle => "TCOD0025",
+            ErrorKind::NestingTooDeep => "TCOD0026",
+        }
+    }
+}
@@ -0,0 +1,34 @@
+//! The struct names and fields through which the types in this crate that need more than
+//! `serde`'s data model offers get it from the deserializers in this crate.
+//!
+//! [`Spanned`] needs the location of its value, [`Documented`] the doc comments before it,
+//! [`Ref`] a place to record the reference and [`Registry`] the deserializer itself. There is no
+//! way to ask a `serde` deserializer for any of that, so these types call `deserialize_struct`
+//! with a struct name or fields that no other type uses. The deserializers in this crate
+//! check for these names before anything else, and hand the type what it needs through a
+//! `MapAccess` of their own instead of deserializing a struct block.
+//!
+//! [`Spanned`]: ../struct.Spanned.html
+//! [`Documented`]: ../struct.Documented.html
+//! [`Ref`]: ../struct.Ref.html
+//! [`Registry`]: ../struct.Registry.html
+
+/// The struct name [`Spanned`] asks for.
+///
+/// [`Spanned`]: ../struct.Spanned.html
+pub(crate) const SPANNED: &str = "$__serde_tcod_config_parser_private_Spanned";
+
+/// The struct name [`Documented`] asks for.
+///
+/// [`Documented`]: ../struct.Documented.html
+pub(crate) const DOCUMENTED: &str = "$__serde_tcod_config_parser_private_Documented";
+
+/// The fields [`Ref`] asks for. The struct name it asks for is the type name of its target.
+///
+/// [`Ref`]: ../struct.Ref.html
+pub(crate) const REF_FIELDS: &[&str] = &["$__serde_tcod_config_parser_private_Ref"];
+
+/// The struct name [`Registry::deserialize`] asks for.
+///
+/// [`Registry::deserialize`]: ../struct.Registry.html#method.deserialize
+pub(crate) const DYNAMIC: &str = "$__serde_tcod_config_parser_private_Dynamic";
@@ -1,38 +1,66 @@
-use crate::lexer::Token;
+use crate::ast::parser::Parser;
+use crate::ast::{doc_comments, doc_text, File, Flag, StructBlock, Value, ValueKind};
+use crate::lexer::{LexicalError, Span, Token};
+use crate::source_map::SourceMap;
 use logos::Lexer;
 use serde::de::Error as DeError;
 use serde::de::{self, Visitor};
 use serde::forward_to_deserialize_any;
 use snafu::{ResultExt, Snafu};
+use std::cell::RefCell;
 use std::fmt::Display;
 use std::ops::Range;
 
 #[macro_use]
 mod macros {
-    macro_rules! unexpected_token {
-        ($l: expr, $e: expr) => {
-            Err(Error::UnexpectedToken {
-                value: $l.slice().to_string(),
-                token_type: format!("{:?}", $l.token),
-                range: $l.range(),
-                expected: $e,
-            })
+    macro_rules! forward_to_file {
+        ($self: ident, $method: ident($($arg: expr),*)) => {
+            $self.resolve_inheritance()?;
+            if let Some(file) = &$self.file {
+                let consumed = std::cell::Cell::new(0);
+                let blocks = file.structs.iter().collect();
+                return StructsDeserializer::new(blocks, file.source, &$self.options, &consumed)
+                    .$method($($arg),*);
+            }
         };
     }
 
-    macro_rules! visit_number {
-        ($l: expr, $to: ident, $ty: ident, $visitor:ident) => {
-            if $l.token == Token::$to {
-                paste::expr! {
-                    let result = $l.slice().parse().unwrap();
-                    $l.advance();
-                    $visitor.[<visit_$ty>](result)
-                }
-            } else {
-                unexpected_token!($l, "<number>")
+    macro_rules! forward_to_custom_type {
+        ($de: expr, $type_name: expr, $method: ident($($arg: expr),*)) => {
+            if let Some(value) = $de.custom_value($type_name) {
+                let value = value?;
+                return ValueDeserializer::new(&value, $de.lexer.source).$method($($arg),*);
+            }
+        };
+    }
+
+    macro_rules! forward_to_item {
+        ($self: ident, $method: ident($($arg: expr),*)) => {
+            if $self.at_value() {
+                return PropertyValueDeserializer::new($self).$method($($arg),*);
+            }
+            if $self.at_flag() {
+                let flag = $self.take_flag();
+                return FlagDeserializer::new(&flag, $self.lexer.source).$method($($arg),*);
             }
         };
     }
+
+    macro_rules! deserialize_item {
+        ($($method: ident)*) => {
+            $(
+                fn $method<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
+                where
+                    V: Visitor<'de>,
+                {
+                    forward_to_file!(self, $method(visitor));
+                    forward_to_item!(self, $method(visitor));
+
+                    self.not_a_value(&visitor)
+                }
+            )*
+        };
+    }
 }
 
 mod struct_internal_access;
@@ -41,17 +69,65 @@ use struct_internal_access::*;
 mod struct_sequence_access;
 use struct_sequence_access::*;
 
-mod primitive_sequence_access;
-use primitive_sequence_access::*;
+mod property_value;
+use property_value::*;
+
+mod ast_deserializer;
+use ast_deserializer::*;
+
+mod custom_types;
+use custom_types::CustomTypes;
+
+#[cfg(feature = "miette")]
+mod diagnostic;
+
+mod documented;
+mod dynamic;
+mod error_kind;
+pub use documented::Documented;
+use documented::DocumentedAccess;
+pub use dynamic::Registry;
+use dynamic::{DynamicAccess, DynamicSource};
+pub use error_kind::ErrorKind;
+
+mod magic_names;
+use magic_names::{DOCUMENTED, DYNAMIC, REF_FIELDS, SPANNED};
+
+mod one_of;
+pub use one_of::{OneOf, ValueList};
+
+mod recovery;
+pub use recovery::Recovered;
+
+mod reference;
+use reference::References;
+pub use reference::{Instance, Ref};
+
+mod spanned;
+pub use spanned::Spanned;
+use spanned::SpannedAccess;
+
+pub(crate) mod suggestions;
+
+mod type_names;
+pub use type_names::TypeNameMatching;
+use type_names::TypeNames;
 
 /// This type represents all possible errors that can occur when deserializing libtcod config files.
+///
+/// With the `miette` feature enabled, this implements [`miette::Diagnostic`], so that errors can be
+/// rendered as reports that point into the config file.
+///
+/// [`miette::Diagnostic`]: https://docs.rs/miette/7/miette/trait.Diagnostic.html
 #[derive(Debug, Snafu)]
 pub enum Error {
     /// An error reported to us by `serde` itself.
-    #[snafu(display("An error was reported by serde: {}", msg))]
+    #[snafu(display("An error was reported by serde{}: {}", at_position(range), msg))]
     Serde {
         /// The message `serde` provided.
         msg: String,
+        /// The location in the source string where the error was encountered, if known.
+        range: Option<Range<usize>>,
     },
     /// A token that was unexpected was encountered.
     #[snafu(display(
@@ -71,13 +147,298 @@ pub enum Error {
         /// The token/value the deserializer was expecting at this location.
         expected: &'static str,
     },
+    /// The source string could not be tokenized.
+    #[snafu(display("Invalid token at position {:?}: {}", range, error))]
+    Lexical {
+        /// What was wrong with the token.
+        error: LexicalError,
+        /// The location in the source string where the problem was encountered.
+        range: Range<usize>,
+    },
+    /// A number could not be represented, e.g. because it is too large.
+    #[snafu(display("Invalid number \"{}\" at position {:?}", value, range))]
+    InvalidNumber {
+        /// The value of the token where this error was triggered.
+        value: String,
+        /// The location in the source string where the token was encountered.
+        range: Range<usize>,
+    },
+    /// The same property was given more than once within a single struct block.
+    #[snafu(display(
+        "Property {} at position {:?} was already given at position {:?}",
+        name,
+        second,
+        first
+    ))]
+    DuplicateProperty {
+        /// The name of the duplicated property.
+        name: String,
+        /// The location in the source string where the property was first given.
+        first: Range<usize>,
+        /// The location in the source string where the property was given again.
+        second: Range<usize>,
+    },
     /// A different struct than was expected was encountered.
-    #[snafu(display("Found struct {}, expected struct {}", name, expected))]
+    #[snafu(display(
+        "Found struct {}{}, expected struct {}{}",
+        name,
+        at_position(range),
+        expected,
+        suggestions::did_you_mean(did_you_mean)
+    ))]
     UnexpectedStruct {
         /// The name of the encountered struct.
         name: String,
         /// The expected name of the struct.
         expected: String,
+        /// The accepted struct names closest to the encountered one, if any are close enough.
+        did_you_mean: Vec<String>,
+        /// The location in the source string where the error was encountered, if known.
+        range: Option<Range<usize>>,
+    },
+    /// A field that does not exist on the type being deserialized was encountered.
+    #[snafu(display(
+        "Found unknown field {}{}{}",
+        name,
+        at_position(range),
+        suggestions::did_you_mean(did_you_mean)
+    ))]
+    UnknownField {
+        /// The name of the encountered field.
+        name: String,
+        /// The names of the fields the type does have.
+        expected: &'static [&'static str],
+        /// The fields closest to the encountered one, if any are close enough.
+        did_you_mean: Vec<String>,
+        /// The location in the source string where the error was encountered, if known.
+        range: Option<Range<usize>>,
+    },
+    /// A field that the type being deserialized requires was not given.
+    #[snafu(display("Missing field {}{}", name, at_position(range)))]
+    MissingField {
+        /// The name of the missing field.
+        name: &'static str,
+        /// The location in the source string where the error was encountered, if known.
+        range: Option<Range<usize>>,
+    },
+    /// A value of the wrong type was given, e.g. a string for an integer field.
+    #[snafu(display(
+        "Invalid type{}: {}, expected {}",
+        at_position(range),
+        unexpected,
+        expected
+    ))]
+    InvalidType {
+        /// A description of the value that was given.
+        unexpected: String,
+        /// A description of what was expected.
+        expected: String,
+        /// The location in the source string where the error was encountered, if known.
+        range: Option<Range<usize>>,
+    },
+    /// A value of the right type, but with an unacceptable value was given.
+    #[snafu(display(
+        "Invalid value{}: {}, expected {}",
+        at_position(range),
+        unexpected,
+        expected
+    ))]
+    InvalidValue {
+        /// A description of the value that was given.
+        unexpected: String,
+        /// A description of what was expected.
+        expected: String,
+        /// The location in the source string where the error was encountered, if known.
+        range: Option<Range<usize>>,
+    },
+    /// A list or a run of struct blocks had the wrong number of elements.
+    #[snafu(display("Invalid length {}{}, expected {}", len, at_position(range), expected))]
+    InvalidLength {
+        /// The number of elements given.
+        len: usize,
+        /// A description of what was expected.
+        expected: String,
+        /// The location in the source string where the error was encountered, if known.
+        range: Option<Range<usize>>,
+    },
+    /// An enum variant that does not exist was given.
+    #[snafu(display(
+        "Found unknown variant {}{}{}",
+        name,
+        at_position(range),
+        suggestions::did_you_mean(did_you_mean)
+    ))]
+    UnknownVariant {
+        /// The name of the encountered variant.
+        name: String,
+        /// The names of the variants the enum does have.
+        expected: &'static [&'static str],
+        /// The variants closest to the encountered one, if any are close enough.
+        did_you_mean: Vec<String>,
+        /// The location in the source string where the error was encountered, if known.
+        range: Option<Range<usize>>,
+    },
+    /// The same field was deserialized more than once.
+    #[snafu(display("Duplicate field {}{}", name, at_position(range)))]
+    DuplicateField {
+        /// The name of the duplicated field.
+        name: &'static str,
+        /// The location in the source string where the error was encountered, if known.
+        range: Option<Range<usize>>,
+    },
+    /// A struct block whose type is not declared in a schema or registered in a [`Registry`], or is
+    /// not allowed where it was given.
+    ///
+    /// [`Registry`]: struct.Registry.html
+    #[snafu(display(
+        "Unknown struct type {} at position {:?}{}",
+        name,
+        range,
+        suggestions::did_you_mean(did_you_mean)
+    ))]
+    UnknownStruct {
+        /// The type name of the struct block.
+        name: String,
+        /// The allowed struct types closest to the encountered one, if any are close enough.
+        did_you_mean: Vec<String>,
+        /// The location of the type name in the source string.
+        range: Range<usize>,
+    },
+    /// A property or flag that is not declared in a schema for its struct type.
+    #[snafu(display(
+        "Unknown property {} of struct {} at position {:?}{}",
+        name,
+        struct_name,
+        range,
+        suggestions::did_you_mean(did_you_mean)
+    ))]
+    UnknownProperty {
+        /// The name of the property or flag.
+        name: String,
+        /// The type name of the struct block it was given in.
+        struct_name: String,
+        /// The declared properties and flags closest to the encountered one, if any are close
+        /// enough.
+        did_you_mean: Vec<String>,
+        /// The location of the name in the source string.
+        range: Range<usize>,
+    },
+    /// A property that a schema declares mandatory was not given.
+    #[snafu(display(
+        "Missing mandatory property {} of struct {} at position {:?}",
+        name,
+        struct_name,
+        range
+    ))]
+    MissingProperty {
+        /// The name of the missing property.
+        name: String,
+        /// The type name of the struct block it is missing from.
+        struct_name: String,
+        /// The location of the type name of the struct block in the source string.
+        range: Range<usize>,
+    },
+    /// A [`Ref`], or a struct block inheriting from another one, points at an instance name that
+    /// no struct block of the right type has.
+    ///
+    /// [`Ref`]: struct.Ref.html
+    #[snafu(display(
+        "Reference to unknown {} {} at position {:?}{}",
+        type_name,
+        name,
+        range,
+        suggestions::did_you_mean(did_you_mean)
+    ))]
+    DanglingReference {
+        /// The instance name that was referenced.
+        name: String,
+        /// The type name of the struct blocks that may be referenced.
+        type_name: String,
+        /// The instance names of that type closest to the referenced one, if any are close enough.
+        did_you_mean: Vec<String>,
+        /// The location of the reference in the source string.
+        range: Range<usize>,
+    },
+    /// A struct block ends up inheriting from itself.
+    #[snafu(display(
+        "Inheritance cycle {} {} at position {:?}",
+        type_name,
+        chain.join(" -> "),
+        range
+    ))]
+    InheritanceCycle {
+        /// The type name of the struct blocks.
+        type_name: String,
+        /// The instance names of the struct blocks in the cycle, in the order they inherit from
+        /// each other, starting and ending with the same one.
+        chain: Vec<String>,
+        /// The location where the cycle is closed in the source string.
+        range: Range<usize>,
+    },
+    /// An error about an item a struct block inherited from another one, or about the struct
+    /// blocks it inherits from, which is located where the item was given.
+    #[snafu(display(
+        "{} (in {} {} at position {:?})",
+        source,
+        type_name,
+        chain.join(" -> "),
+        block
+    ))]
+    Inherited {
+        /// The error about the inherited item.
+        source: Box<Error>,
+        /// The type name of the struct blocks.
+        type_name: String,
+        /// The instance names of the inheriting struct block and the struct blocks it inherits
+        /// from, in the order they inherit from each other.
+        chain: Vec<String>,
+        /// The location of the type name of the inheriting struct block in the source string.
+        block: Range<usize>,
+    },
+    /// Struct blocks and lists are nested more than [`MAX_DEPTH`] levels deep.
+    ///
+    /// [`MAX_DEPTH`]: constant.MAX_DEPTH.html
+    #[snafu(display(
+        "Struct blocks and lists nested more than {} levels deep at position {:?}",
+        MAX_DEPTH,
+        range
+    ))]
+    NestingTooDeep {
+        /// The location of the `{` or `[` that is nested too deeply.
+        range: Range<usize>,
+    },
+    /// A file named by an `include` directive could not be loaded.
+    #[snafu(display(
+        "Could not include {}{}: {}",
+        path,
+        included_from(file, range),
+        message
+    ))]
+    Include {
+        /// The path of the file.
+        path: String,
+        /// The path of the file including it, unless it is the file being expanded.
+        file: Option<String>,
+        /// Why the file could not be loaded.
+        message: String,
+        /// The location of the directive within the including file.
+        range: Option<Range<usize>>,
+    },
+    /// A file ends up including itself.
+    #[snafu(display(
+        "Include cycle {} in {} at position {:?}",
+        chain.join(" -> "),
+        file,
+        range
+    ))]
+    IncludeCycle {
+        /// The paths of the files in the cycle, in the order they include each other, starting and
+        /// ending with the same one.
+        chain: Vec<String>,
+        /// The path of the file where the cycle is closed.
+        file: String,
+        /// The location of the directive closing the cycle within that file.
+        range: Range<usize>,
     },
     /// All structs must have an `instance_name` field. This field is used to hold the value within
     /// `libtcod_struct_name "libtcod_instance_name" { ... }`. Structs without an instance name will
@@ -89,6 +450,18 @@ pub enum Error {
         /// The cause of the invalid char.
         source: InvalidCharError,
     },
+    /// Nothing exists at the given path.
+    #[snafu(display("Nothing found at path {}", path))]
+    PathNotFound {
+        /// The path that was looked up.
+        path: String,
+    },
+    /// A value cannot be written in a libtcod config file, e.g. a string containing `"`.
+    #[snafu(display("The value {} cannot be represented in a libtcod config file", value))]
+    UnrepresentableValue {
+        /// A description of the value.
+        value: String,
+    },
     /// This format supports multi-line strings, but they are not necessarily contiguous, so if such
     /// an non-contiguous variant is encountered on a string slice field, this error is returned.
     #[snafu(display("multi-line string is not supported for borrowed str fields"))]
@@ -119,282 +492,870 @@ impl DeError for Error {
     {
         Error::Serde {
             msg: format!("{}", msg),
+            range: None,
         }
     }
-}
-
-/// A re-declaration of `Result` that sets sensible defaults for `T` and `E`
-pub type Result<T = (), E = Error> = std::result::Result<T, E>;
-
-/// A structure that deserializes libtcod config file values into Rust values.
-pub struct Deserializer<'de> {
-    lexer: Lexer<Token, &'de str>,
-}
-
-impl<'de> Deserializer<'de> {
-    /// Create a libtcod config file deserializer from a `&str`.
-    ///
-    /// Typically it is more convenient to use the [`Deserializer::from_str`] function instead
-    ///
-    /// [`Deserializer::from_str`]: #method.from_str
-    pub fn new(source: &'de str) -> Self {
-        use logos::Logos;
 
-        let lexer = Token::lexer(source);
-        Self { lexer }
+    fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
+        Error::UnknownField {
+            name: field.to_string(),
+            expected,
+            did_you_mean: suggestions::closest(field, expected.iter().copied()),
+            range: None,
+        }
     }
 
-    /// Creates a libtcod config file deserializer from a `&str`.
-    #[allow(clippy::should_implement_trait)]
-    pub fn from_str<T: de::Deserialize<'de>>(s: &'de str) -> Result<T> {
-        T::deserialize(&mut Deserializer::new(s))
+    fn missing_field(field: &'static str) -> Self {
+        Error::MissingField {
+            name: field,
+            range: None,
+        }
     }
-}
 
-impl<'de: 'a, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
-    type Error = Error;
+    fn invalid_type(unexpected: de::Unexpected, expected: &dyn de::Expected) -> Self {
+        Error::InvalidType {
+            unexpected: unexpected.to_string(),
+            expected: expected.to_string(),
+            range: None,
+        }
+    }
 
-    forward_to_deserialize_any! {
-        bytes
-        byte_buf
-        unit
-        unit_struct
-        newtype_struct
-        tuple
-        tuple_struct
-        map
-        enum
-        identifier
+    fn invalid_value(unexpected: de::Unexpected, expected: &dyn de::Expected) -> Self {
+        Error::InvalidValue {
+            unexpected: unexpected.to_string(),
+            expected: expected.to_string(),
+            range: None,
+        }
     }
 
-    fn deserialize_any<V>(self, _visitor: V) -> Result<<V as Visitor<'de>>::Value>
-    where
-        V: Visitor<'de>,
-    {
-        unimplemented!("not supported")
+    fn invalid_length(len: usize, expected: &dyn de::Expected) -> Self {
+        Error::InvalidLength {
+            len,
+            expected: expected.to_string(),
+            range: None,
+        }
     }
 
-    fn deserialize_bool<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
-    where
-        V: Visitor<'de>,
-    {
-        if self.lexer.token == Token::Identifier {
-            self.lexer.advance();
-            visitor.visit_bool(true)
-        } else {
-            unexpected_token!(self.lexer, "<identifier>")
+    fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> Self {
+        Error::UnknownVariant {
+            name: variant.to_string(),
+            expected,
+            did_you_mean: suggestions::closest(variant, expected.iter().copied()),
+            range: None,
         }
     }
 
-    fn deserialize_i8<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
-    where
-        V: Visitor<'de>,
-    {
-        visit_number!(self.lexer, Integer, i8, visitor)
+    fn duplicate_field(field: &'static str) -> Self {
+        Error::DuplicateField {
+            name: field,
+            range: None,
+        }
     }
+}
 
-    fn deserialize_i16<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
-    where
-        V: Visitor<'de>,
-    {
-        visit_number!(self.lexer, Integer, i16, visitor)
+impl Error {
+    /// A stable code identifying the kind of error, e.g. `TCOD0003`. See [`ErrorKind::code`].
+    ///
+    /// [`ErrorKind::code`]: enum.ErrorKind.html#method.code
+    pub fn code(&self) -> &'static str {
+        self.kind().code()
     }
 
-    fn deserialize_i32<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
-    where
-        V: Visitor<'de>,
-    {
-        visit_number!(self.lexer, Integer, i32, visitor)
+    /// The kind of error, for matching on errors without caring about their details.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Serde { .. } => ErrorKind::Custom,
+            Error::UnexpectedToken { .. } => ErrorKind::UnexpectedToken,
+            Error::Lexical { .. } => ErrorKind::Lexical,
+            Error::InvalidNumber { .. } => ErrorKind::InvalidNumber,
+            Error::DuplicateProperty { .. } => ErrorKind::DuplicateProperty,
+            Error::UnexpectedStruct { .. } => ErrorKind::UnexpectedStruct,
+            Error::UnknownField { .. } => ErrorKind::UnknownField,
+            Error::MissingField { .. } => ErrorKind::MissingField,
+            Error::InvalidType { .. } => ErrorKind::InvalidType,
+            Error::InvalidValue { .. } => ErrorKind::InvalidValue,
+            Error::InvalidLength { .. } => ErrorKind::InvalidLength,
+            Error::UnknownVariant { .. } => ErrorKind::UnknownVariant,
+            Error::DuplicateField { .. } => ErrorKind::DuplicateField,
+            Error::UnknownStruct { .. } => ErrorKind::UnknownStruct,
+            Error::UnknownProperty { .. } => ErrorKind::UnknownProperty,
+            Error::MissingProperty { .. } => ErrorKind::MissingProperty,
+            Error::DanglingReference { .. } => ErrorKind::DanglingReference,
+            Error::InheritanceCycle { .. } => ErrorKind::InheritanceCycle,
+            Error::Inherited { source, .. } => source.kind(),
+            Error::Include { .. } => ErrorKind::Include,
+            Error::IncludeCycle { .. } => ErrorKind::IncludeCycle,
+            Error::NestingTooDeep { .. } => ErrorKind::NestingTooDeep,
+            Error::MissingInstanceName => ErrorKind::MissingInstanceName,
+            Error::InvalidChar { .. } => ErrorKind::InvalidChar,
+            Error::PathNotFound { .. } => ErrorKind::PathNotFound,
+            Error::UnrepresentableValue { .. } => ErrorKind::UnrepresentableValue,
+            Error::MultiLineStringOnBorrowedStr { .. } => ErrorKind::MultiLineStringOnBorrowedStr,
+        }
     }
 
-    fn deserialize_i64<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
-    where
-        V: Visitor<'de>,
-    {
-        visit_number!(self.lexer, Integer, i64, visitor)
+    /// The location in the source string where the error was encountered, if known.
+    pub fn range(&self) -> Option<Range<usize>> {
+        match self {
+            Error::UnexpectedToken { range, .. }
+            | Error::Lexical { range, .. }
+            | Error::InvalidNumber { range, .. }
+            | Error::UnknownStruct { range, .. }
+            | Error::UnknownProperty { range, .. }
+            | Error::MissingProperty { range, .. }
+            | Error::DanglingReference { range, .. }
+            | Error::InheritanceCycle { range, .. }
+            | Error::IncludeCycle { range, .. }
+            | Error::NestingTooDeep { range }
+            | Error::MultiLineStringOnBorrowedStr { range, .. } => Some(range.clone()),
+            Error::DuplicateProperty { second, .. } => Some(second.clone()),
+            Error::Serde { range, .. }
+            | Error::UnexpectedStruct { range, .. }
+            | Error::UnknownField { range, .. }
+            | Error::MissingField { range, .. }
+            | Error::InvalidType { range, .. }
+            | Error::InvalidValue { range, .. }
+            | Error::InvalidLength { range, .. }
+            | Error::UnknownVariant { range, .. }
+            | Error::DuplicateField { range, .. }
+            | Error::Include { range, .. } => range.clone(),
+            Error::Inherited { source, .. } => source.range(),
+            _ => None,
+        }
     }
 
-    fn deserialize_u8<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
-    where
-        V: Visitor<'de>,
-    {
-        visit_number!(self.lexer, Integer, u8, visitor)
+    /// Attaches the location `at` to errors that are raised without one, like those created
+    /// through `serde`, unless the error already has a location.
+    pub(crate) fn at(mut self, at: &Range<usize>) -> Self {
+        match &mut self {
+            Error::Serde { range, .. }
+            | Error::UnexpectedStruct { range, .. }
+            | Error::UnknownField { range, .. }
+            | Error::MissingField { range, .. }
+            | Error::InvalidType { range, .. }
+            | Error::InvalidValue { range, .. }
+            | Error::InvalidLength { range, .. }
+            | Error::UnknownVariant { range, .. }
+            | Error::DuplicateField { range, .. } => {
+                range.get_or_insert_with(|| at.clone());
+            }
+            _ => {}
+        }
+        self
     }
 
-    fn deserialize_u16<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
-    where
-        V: Visitor<'de>,
-    {
-        visit_number!(self.lexer, Integer, u16, visitor)
+    /// Names the struct blocks `block` inherits from in an error about one of the items it
+    /// inherited, which is located outside of it.
+    pub(crate) fn inherited_by(self, block: &StructBlock<'_>) -> Self {
+        let inherited = match self.range() {
+            Some(range) => range.start < block.span.start || range.end > block.span.end,
+            None => false,
+        };
+        if !inherited || block.ancestors.is_empty() || matches!(self, Error::Inherited { .. }) {
+            return self;
+        }
+
+        let instance_name = block.instance_name.unwrap_or_default();
+        let ancestors = block.inherited_from();
+        Error::Inherited {
+            source: Box::new(self),
+            type_name: block.type_name.to_string(),
+            chain: std::iter::once(instance_name)
+                .chain(ancestors)
+                .map(|name| name.to_string())
+                .collect(),
+            block: block.type_name_span.clone(),
+        }
     }
 
-    fn deserialize_u32<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
-    where
-        V: Visitor<'de>,
-    {
-        visit_number!(self.lexer, Integer, u32, visitor)
+    /// A hint on how to fix the error, if there is one.
+    pub fn help(&self) -> Option<String> {
+        match self {
+            Error::UnexpectedStruct { did_you_mean, .. }
+            | Error::UnknownField { did_you_mean, .. }
+            | Error::UnknownVariant { did_you_mean, .. }
+            | Error::UnknownStruct { did_you_mean, .. }
+            | Error::UnknownProperty { did_you_mean, .. }
+            | Error::DanglingReference { did_you_mean, .. }
+                if !did_you_mean.is_empty() =>
+            {
+                Some(suggestions::did_you_mean(did_you_mean)[2..].to_string())
+            }
+            Error::UnknownField { expected, .. } | Error::UnknownVariant { expected, .. }
+                if !expected.is_empty() =>
+            {
+                Some(format!("expected one of: {}", expected.join(", ")))
+            }
+            Error::DuplicateProperty { .. } => Some(
+                "remove one of them, or allow overriding properties with `last_property_wins`"
+                    .to_string(),
+            ),
+            Error::MissingInstanceName => {
+                Some("add an `instance_name: String` field to the struct".to_string())
+            }
+            Error::MultiLineStringOnBorrowedStr { .. } => {
+                Some("use `String` instead of `&str` for the field".to_string())
+            }
+            Error::Inherited { source, .. } => source.help(),
+            _ => None,
+        }
     }
+}
 
-    fn deserialize_u64<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
-    where
-        V: Visitor<'de>,
-    {
-        visit_number!(self.lexer, Integer, u64, visitor)
+impl InvalidCharError {
+    /// A stable code identifying the kind of error, like [`Error::code`].
+    ///
+    /// [`Error::code`]: enum.Error.html#method.code
+    pub fn code(&self) -> &'static str {
+        match self {
+            InvalidCharError::ParseInt { .. } => "TCOD0101",
+            InvalidCharError::InvalidEscapeSequence { .. } => "TCOD0102",
+            InvalidCharError::InvalidCharValue { .. } => "TCOD0103",
+        }
     }
+}
 
-    fn deserialize_f32<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
-    where
-        V: Visitor<'de>,
-    {
-        visit_number!(self.lexer, Float, f32, visitor)
+/// Formats an optional location for an error message.
+fn at_position(range: &Option<Range<usize>>) -> String {
+    match range {
+        Some(range) => format!(" at position {:?}", range),
+        None => String::new(),
     }
+}
 
-    fn deserialize_f64<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
-    where
-        V: Visitor<'de>,
-    {
-        visit_number!(self.lexer, Float, f64, visitor)
+/// Formats where a file was included from for an error message.
+fn included_from(file: &Option<String>, range: &Option<Range<usize>>) -> String {
+    match file {
+        Some(file) => format!(" from {}{}", file, at_position(range)),
+        None => String::new(),
     }
+}
 
-    fn deserialize_char<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
-    where
-        V: Visitor<'de>,
-    {
-        if self.lexer.token == Token::Integer {
-            // Decimal notation
-            let result = self
-                .lexer
-                .slice()
-                .parse::<u8>()
-                .context(ParseInt)
-                .context(InvalidChar)? as char;
-            self.lexer.advance();
-            visitor.visit_char(result)
-        } else if self.lexer.token == Token::Hex {
-            // Hexadecimal notation
-            let result = u8::from_str_radix(&self.lexer.slice()[2..], 16)
-                .context(ParseInt)
-                .context(InvalidChar)? as char;
-            self.lexer.advance();
-            visitor.visit_char(result)
-        } else if self.lexer.token == Token::Char {
-            let result = self.lexer.slice();
-            let result = &result[1..][..result.len() - 2];
-            let chars = result.chars().collect::<Vec<_>>();
-            let octal = chars.len() > 1 && chars.iter().skip(1).all(|c| c.is_digit(8));
-
-            let result = match result {
-                c if c.starts_with("\\x") => {
-                    // Hexadecimal notation
-                    let c = &c[2..];
-                    u8::from_str_radix(c, 16)
-                        .context(ParseInt)
-                        .context(InvalidChar)? as char
-                }
-                c if octal => {
-                    // Octal notation
-                    let c = &c[1..];
-                    u8::from_str_radix(c, 8)
-                        .context(ParseInt)
-                        .context(InvalidChar)? as char
+/// How deeply struct blocks and lists can be nested, counting both. Deeper nesting is reported as
+/// an [`Error::NestingTooDeep`] instead of running out of stack.
+///
+/// [`Error::NestingTooDeep`]: enum.Error.html#variant.NestingTooDeep
+pub const MAX_DEPTH: usize = 128;
+
+/// A re-declaration of `Result` that sets sensible defaults for `T` and `E`
+pub type Result<T = (), E = Error> = std::result::Result<T, E>;
+
+/// Parses the char represented by an integer, hexadecimal or char token.
+pub(crate) fn parse_char(token: Token, slice: &str) -> Result<char> {
+    if token == Token::Integer {
+        // Decimal notation
+        Ok(slice.parse::<u8>().context(ParseInt).context(InvalidChar)? as char)
+    } else if token == Token::Hex {
+        // Hexadecimal notation
+        Ok(u8::from_str_radix(&slice[2..], 16)
+            .context(ParseInt)
+            .context(InvalidChar)? as char)
+    } else {
+        let result = &slice[1..][..slice.len() - 2];
+        let chars = result.chars().collect::<Vec<_>>();
+        let octal = chars.len() > 1 && chars.iter().skip(1).all(|c| c.is_digit(8));
+
+        let result = match result {
+            c if c.starts_with("\\x") => {
+                // Hexadecimal notation
+                let c = &c[2..];
+                u8::from_str_radix(c, 16)
+                    .context(ParseInt)
+                    .context(InvalidChar)? as char
+            }
+            c if octal => {
+                // Octal notation
+                let c = &c[1..];
+                u8::from_str_radix(c, 8)
+                    .context(ParseInt)
+                    .context(InvalidChar)? as char
+            }
+            c if c.starts_with('\\') && c.len() == 2 => {
+                // Special characters
+                match &c[1..] {
+                    "n" => '\n',
+                    "t" => '\t',
+                    "r" => '\r',
+                    "\\" => '\\',
+                    "\"" => '"',
+                    "'" => '\'',
+                    s => {
+                        return Err(InvalidCharError::InvalidEscapeSequence {
+                            value: s.to_string(),
+                        })
+                        .context(InvalidChar)
+                    }
                 }
-                c if c.starts_with('\\') && c.len() == 2 => {
-                    // Special characters
-                    match &c[1..] {
-                        "n" => '\n',
-                        "t" => '\t',
-                        "r" => '\r',
-                        "\\" => '\\',
-                        "\"" => '"',
-                        "'" => '\'',
-                        s => {
-                            return Err(InvalidCharError::InvalidEscapeSequence {
-                                value: s.to_string(),
-                            })
-                            .context(InvalidChar)
-                        }
+            }
+            c => {
+                let mut chars = c.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => {
+                        return Err(InvalidCharError::InvalidCharValue {
+                            value: c.to_string(),
+                        })
+                        .context(InvalidChar)
                     }
                 }
-                c => c.parse().unwrap(),
-            };
+            }
+        };
 
-            self.lexer.advance();
-            visitor.visit_char(result)
-        } else {
-            unexpected_token!(self.lexer, "\"<char>\"")
-        }
+        Ok(result)
     }
+}
 
-    fn deserialize_str<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
-    where
-        V: Visitor<'de>,
-    {
-        if self.lexer.token == Token::Text {
-            let result = self.lexer.slice();
-            let result = &result[1..][..result.len() - 2];
-            self.lexer.advance();
-
-            if self.lexer.token == Token::Text {
-                return Err(Error::MultiLineStringOnBorrowedStr {
-                    value: self.lexer.slice().to_string(),
-                    range: self.lexer.range(),
-                });
-            }
+/// A structure that deserializes libtcod config file values into Rust values.
+pub struct Deserializer<'de> {
+    lexer: Lexer<Token, &'de str>,
+    file: Option<File<'de>>,
+    options: Options,
+    previous_end: usize,
+    comments: Vec<&'de str>,
+    /// How many struct blocks and lists the current token is within.
+    depth: usize,
+}
 
-            visitor.visit_borrowed_str(result)
-        } else {
-            unexpected_token!(self.lexer, "\"<string>\"")
+/// The settings that affect how a [`Deserializer`] interprets a config file.
+///
+/// [`Deserializer`]: struct.Deserializer.html
+#[derive(Debug, Default)]
+pub(crate) struct Options {
+    pub(crate) last_property_wins: bool,
+    pub(crate) type_names: TypeNames,
+    pub(crate) custom_types: CustomTypes,
+    pub(crate) references: References,
+    /// The property naming the struct block a struct block inherits from, until the inheritance
+    /// has been resolved.
+    pub(crate) inheritance: Option<String>,
+    /// The location of the innermost item of the syntax tree that failed to deserialize, so that
+    /// it can be left out when recovering from errors.
+    pub(crate) failed_item: RefCell<Option<Span>>,
+}
+
+impl Options {
+    /// Remembers `span` as the location of the failed item, unless an item within it has already
+    /// been blamed.
+    pub(crate) fn blame<T>(&self, span: &Span, result: Result<T>) -> Result<T> {
+        if result.is_err() {
+            self.failed_item
+                .borrow_mut()
+                .get_or_insert_with(|| span.clone());
         }
+        result
     }
+}
 
-    fn deserialize_string<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
-    where
-        V: Visitor<'de>,
-    {
-        if self.lexer.token != Token::Text {
-            return unexpected_token!(self.lexer, "\"<string>\"");
-        }
+impl<'de> Deserializer<'de> {
+    /// Create a libtcod config file deserializer from a `&str`.
+    ///
+    /// Typically it is more convenient to use the [`Deserializer::from_str`] function instead
+    ///
+    /// [`Deserializer::from_str`]: #method.from_str
+    pub fn new(source: &'de str) -> Self {
+        use logos::Logos;
 
-        let mut result = String::new();
-        while self.lexer.token == Token::Text {
-            let slice = self.lexer.slice();
-            let slice = &slice[1..][..slice.len() - 2];
-            result.push_str(slice);
-            self.lexer.advance();
+        let lexer = Token::lexer(source);
+        Self {
+            lexer,
+            file: None,
+            options: Options::default(),
+            previous_end: 0,
+            comments: Vec::new(),
+            depth: 0,
         }
-        visitor.visit_string(result)
     }
 
-    fn deserialize_option<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
-    where
-        V: Visitor<'de>,
-    {
-        visitor.visit_some(self)
+    /// Create a libtcod config file deserializer from an already parsed [`File`].
+    ///
+    /// [`File`]: ../ast/struct.File.html
+    pub fn from_file(file: File<'de>) -> Self {
+        let mut result = Self::new("");
+        result.file = Some(file);
+        result
+    }
+
+    /// Allow a property to be given more than once within the same struct block, in which case
+    /// only the last value given is used. This is useful for files that are layered on top of each
+    /// other.
+    ///
+    /// By default, a repeated property results in an [`Error::DuplicateProperty`].
+    ///
+    /// [`Error::DuplicateProperty`]: enum.Error.html#variant.DuplicateProperty
+    pub fn last_property_wins(mut self, last_property_wins: bool) -> Self {
+        self.options.last_property_wins = last_property_wins;
+        self
+    }
+
+    /// Set how struct type names in the config file are compared against the names of the types
+    /// being deserialized.
+    ///
+    /// By default, type names must match exactly.
+    pub fn type_name_matching(mut self, matching: TypeNameMatching) -> Self {
+        self.options.type_names.matching = matching;
+        self
+    }
+
+    /// Accept `alias` as a struct type name for the type whose (serde) name is `type_name`.
+    ///
+    /// This is useful for loading files written for different versions of the same program, e.g.
+    /// `.type_name_alias("item_type", "ItemType")` accepts both `item_type "Sword" { ... }` and
+    /// `ItemType "Sword" { ... }` for a type declared with `#[serde(rename = "item_type")]`.
+    /// Aliases are compared according to [`type_name_matching`].
+    ///
+    /// [`type_name_matching`]: #method.type_name_matching
+    pub fn type_name_alias(mut self, type_name: &'static str, alias: impl Into<String>) -> Self {
+        self.options.type_names.add_alias(type_name, alias.into());
+        self
     }
 
-    fn deserialize_seq<V>(mut self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
+    /// Parse the values of the type whose (serde) name is `type_name` with `parser`, like
+    /// libtcod's `TCOD_parser_new_custom_type`, so that game-specific literals don't have to be
+    /// written as strings.
+    ///
+    /// `parser` is given the raw text of the value, e.g. `3:4` for `pos = 3:4`, or `"3:4"` with
+    /// the quotes for `pos = "3:4"`. The raw text runs up to the end of the line, a comment, a `,`,
+    /// `]` or `}` that is not within quotes, or the next property, flag or struct block, i.e. a
+    /// word after whitespace, and must start like a regular value, e.g. with a number or a quote.
+    /// `parser` returns the value that the type is then deserialized from as usual, or a
+    /// description of what was expected, which is reported as an [`Error::InvalidValue`] pointing
+    /// at the raw text. The spans of the returned values don't
+    /// matter, as they are all replaced with the location of the raw text.
+    ///
+    /// Only newtype structs, tuple structs, structs and enums have names that can be matched. When
+    /// deserializing from an already parsed [`File`], `parser` is given the text of values that
+    /// could be parsed as regular values.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_derive::Deserialize;
+    /// use serde_tcod_config_parser::ast::{ListValue, Value, ValueKind};
+    /// use serde_tcod_config_parser::de::Deserializer;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Pos(i64, i64);
+    ///
+    /// #[derive(Deserialize)]
+    /// #[serde(rename = "spawn")]
+    /// struct Spawn {
+    ///     instance_name: String,
+    ///     pos: Pos,
+    /// }
+    ///
+    /// let parse_pos = |raw: &str| {
+    ///     let (x, y) = raw.split_once(':').ok_or("a position like `x:y`")?;
+    ///     let coordinate = |c: &str| c.trim().parse().map_err(|_| "an integer coordinate");
+    ///     let values = [coordinate(x)?, coordinate(y)?]
+    ///         .iter()
+    ///         .map(|&c| Value { kind: ValueKind::Integer(c), span: 0..0 })
+    ///         .collect();
+    ///     Ok(ValueKind::List(ListValue { values, span: 0..0 }))
+    /// };
+    ///
+    /// let source = r#"spawn "Orc" { pos = 3:4 }"#;
+    /// let mut de = Deserializer::new(source).custom_type("Pos", parse_pos);
+    /// let spawn = Spawn::deserialize(&mut de).unwrap();
+    /// assert_eq!((spawn.pos.0, spawn.pos.1), (3, 4));
+    /// ```
+    ///
+    /// [`Error::InvalidValue`]: enum.Error.html#variant.InvalidValue
+    /// [`File`]: ../ast/struct.File.html
+    pub fn custom_type<F>(mut self, type_name: &'static str, parser: F) -> Self
     where
-        V: Visitor<'de>,
+        F: Fn(&str) -> std::result::Result<ValueKind<'static>, String> + Send + 'static,
     {
+        self.options.custom_types.add(type_name, Box::new(parser));
+        self
+    }
+
+    /// Deserialize the struct blocks of all the types registered in `registry` into the field
+    /// `field`, which is typically a `Vec<Box<T>>`. See [`Registry`] for an example.
+    ///
+    /// This accepts the struct type names of the registered types as aliases for `field`, like
+    /// [`type_name_alias`] does.
+    ///
+    /// [`Registry`]: struct.Registry.html
+    /// [`type_name_alias`]: #method.type_name_alias
+    pub fn registry_field<T: ?Sized + 'static>(
+        mut self,
+        field: &'static str,
+        registry: &Registry<T>,
+    ) -> Self {
+        for type_name in registry.type_names() {
+            self.options
+                .type_names
+                .add_alias(field, type_name.to_string());
+        }
+        self
+    }
+
+    /// Let struct blocks inherit the properties, flags and struct blocks they don't give
+    /// themselves from another struct block of the same type, named by the property `property`,
+    /// e.g. `monster "Orc" { inherits = "BaseOrc" hp = 12 }` for `.inheritance("inherits")`.
+    ///
+    /// The inheritance is resolved on the syntax tree before anything is deserialized, as
+    /// described for [`ast::resolve_inheritance`], so the whole file is parsed up front.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_derive::Deserialize;
+    /// use serde_tcod_config_parser::de::Deserializer;
+    ///
+    /// #[derive(Deserialize)]
+    /// #[serde(rename = "monster")]
+    /// struct Monster {
+    ///     instance_name: String,
+    ///     hp: i32,
+    ///     speed: i32,
+    /// }
+    ///
+    /// let source = r#"
+    ///     monster "BaseOrc" { hp = 10 speed = 2 }
+    ///     monster "Orc" { inherits = "BaseOrc" hp = 12 }
+    /// "#;
+    /// let mut de = Deserializer::new(source).inheritance("inherits");
+    /// let monsters = Vec::<Monster>::deserialize(&mut de).unwrap();
+    /// assert_eq!((monsters[1].hp, monsters[1].speed), (12, 2));
+    /// ```
+    ///
+    /// [`ast::resolve_inheritance`]: ../ast/fn.resolve_inheritance.html
+    pub fn inheritance(mut self, property: impl Into<String>) -> Self {
+        self.options.inheritance = Some(property.into());
+        self
+    }
+
+    /// Checks that every [`Ref`] deserialized so far points at a struct block of the right type
+    /// that was deserialized as well, returning the first one that doesn't. See
+    /// [`dangling_references`] to get all of them.
+    ///
+    /// [`Ref`]: struct.Ref.html
+    /// [`dangling_references`]: #method.dangling_references
+    pub fn check_references(&self) -> Result<()> {
+        match self.dangling_references().into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// An [`Error::DanglingReference`] for every [`Ref`] deserialized so far that doesn't point at
+    /// a struct block of the right type that was deserialized as well, in the order they were
+    /// deserialized.
+    ///
+    /// [`Error::DanglingReference`]: enum.Error.html#variant.DanglingReference
+    /// [`Ref`]: struct.Ref.html
+    pub fn dangling_references(&self) -> Vec<Error> {
+        self.options.references.dangling()
+    }
+
+    /// Creates a libtcod config file deserializer from a `&str`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str<T: de::Deserialize<'de>>(s: &'de str) -> Result<T> {
+        T::deserialize(&mut Deserializer::new(s))
+    }
+
+    /// Creates a libtcod config file deserializer from a `&str`, and also returns a [`SourceMap`]
+    /// that tells where every struct block, property, flag and list element was given.
+    ///
+    /// [`SourceMap`]: ../source_map/struct.SourceMap.html
+    pub fn from_str_with_source_map<T: de::Deserialize<'de>>(
+        s: &'de str,
+    ) -> Result<(T, SourceMap)> {
+        let file = crate::ast::parse(s)?;
+        let source_map = SourceMap::new(&file);
+        let value = T::deserialize(&mut Deserializer::from_file(file))?;
+        Ok((value, source_map))
+    }
+
+    /// Parses the whole file and resolves the inheritance between its struct blocks, if that
+    /// was asked for and hasn't been done yet.
+    fn resolve_inheritance(&mut self) -> Result<()> {
+        if let Some(property) = self.options.inheritance.take() {
+            let file = match self.file.take() {
+                Some(file) => file,
+                None => crate::ast::parse(self.lexer.source)?,
+            };
+            let file = self.file.insert(file);
+            crate::ast::resolve_inheritance(file, &property)?;
+        }
+        Ok(())
+    }
+
+    /// Parses the value at the current token with the parser registered for `type_name`, if there
+    /// is one, and moves on past it.
+    fn custom_value(&mut self, type_name: &str) -> Option<Result<Value<'static>>> {
+        if !self.options.custom_types.contains(type_name) {
+            return None;
+        }
+
+        let span = custom_types::raw_value(self.lexer.source, self.lexer.range().start);
+        while self.lexer.token != Token::EndOfProgram && self.lexer.range().start < span.end {
+            self.advance();
+        }
+        let raw = &self.lexer.source[span.clone()];
+        self.options.custom_types.parse(type_name, raw, &span)
+    }
+
+    /// Moves on to the next token, remembering where the current one ended so that the comments
+    /// in between can be found.
+    fn advance(&mut self) {
+        self.previous_end = self.lexer.range().end;
+        self.lexer.advance();
+    }
+
+    /// Remembers the doc comments leading up to the current token, for the value that follows.
+    fn take_comments(&mut self) {
+        self.comments = doc_comments(
+            self.lexer.source,
+            self.previous_end,
+            self.lexer.range().start,
+        );
+    }
+
+    /// Whether the current token starts a property value or list element.
+    fn at_value(&self) -> bool {
+        matches!(
+            self.lexer.token,
+            Token::Text
+                | Token::Char
+                | Token::Integer
+                | Token::Hex
+                | Token::Float
+                | Token::Color
+                | Token::BracketOpen
+        )
+    }
+
+    /// Whether the current token is a flag, i.e. an identifier that doesn't start a struct block.
+    fn at_flag(&self) -> bool {
+        let mut next = self.lexer.clone();
+        next.advance();
+        self.lexer.token == Token::Identifier
+            && !matches!(next.token, Token::Text | Token::BraceOpen)
+    }
+
+    /// Takes the flag at the current token, with the doc comments leading up to it, and moves on
+    /// past it.
+    fn take_flag(&mut self) -> Flag<'de> {
+        let flag = Flag {
+            comments: std::mem::take(&mut self.comments),
+            name: self.lexer.slice(),
+            span: self.lexer.range(),
+        };
+        self.advance();
+        flag
+    }
+
+    /// The error for deserializing the struct block or token at the current token as a value,
+    /// which it is neither.
+    fn not_a_value<T>(&self, expected: &dyn de::Expected) -> Result<T> {
         if self.lexer.token == Token::Identifier {
-            visitor.visit_seq(StructSeqAccess::new(&mut self))
-        } else if self.lexer.token == Token::BracketOpen {
-            self.lexer.advance();
-            let result = visitor.visit_seq(PrimitiveSeqAccess::new(&mut self))?;
+            Err(de::Error::invalid_type(
+                de::Unexpected::Other("struct block"),
+                expected,
+            ))
+        } else {
+            unexpected_token!(self.lexer, "<value>")
+        }
+    }
 
-            if self.lexer.token != Token::BracketClose {
-                return unexpected_token!(self.lexer, "]");
+    /// Parses a single property value, i.e. a (multi-line) string, a char, a number, a color or a
+    /// list of values, and moves on past it.
+    fn parse_value(&mut self) -> Result<Value<'de>> {
+        let mut parser = Parser::at(self.lexer.clone());
+        parser.nested_within(self.depth);
+        let value = parser.parse_value()?;
+        self.lexer = parser.into_lexer();
+        self.previous_end = value.span.end;
+        Ok(value)
+    }
+
+    /// Skips over a single property value, like [`parse_value`].
+    ///
+    /// [`parse_value`]: #method.parse_value
+    fn skip_value(&mut self) -> Result {
+        self.parse_value().map(drop)
+    }
+
+    /// Runs `deserialize` on a struct block or list, which must not be nested too deeply.
+    fn nested<T>(&mut self, deserialize: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth == MAX_DEPTH {
+            return Err(Error::NestingTooDeep {
+                range: self.lexer.range(),
+            });
+        }
+
+        self.depth += 1;
+        let result = deserialize(self);
+        self.depth -= 1;
+        result
+    }
+}
+
+/// Finds the location of the property value, flag or struct block starting at `lexer`.
+fn value_span(mut lexer: Lexer<Token, &str>) -> Result<Span> {
+    let start = lexer.range().start;
+    let mut end = lexer.range().end;
+    match lexer.token {
+        Token::Text => {
+            while lexer.token == Token::Text {
+                end = lexer.range().end;
+                lexer.advance();
             }
-            self.lexer.advance();
+        }
+        Token::BracketOpen => end = balanced_end(lexer, Token::BracketOpen, Token::BracketClose)?,
+        Token::Identifier => {
+            lexer.advance();
+            if lexer.token == Token::Text {
+                lexer.advance();
+            }
+            if lexer.token == Token::BraceOpen {
+                end = balanced_end(lexer, Token::BraceOpen, Token::BraceClose)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(start..end)
+}
+
+/// Attaches the location of the property value, flag or struct block starting at `lexer` to
+/// `error`, like [`Error::at`] does.
+///
+/// [`Error::at`]: enum.Error.html#method.at
+fn at_value_span(error: Error, lexer: Lexer<Token, &str>) -> Error {
+    match value_span(lexer) {
+        Ok(span) => error.at(&span),
+        Err(too_deep) => too_deep,
+    }
+}
 
-            Ok(result)
+/// Finds the end of the `open` token at `lexer` and everything up to its matching `close` token.
+fn balanced_end(mut lexer: Lexer<Token, &str>, open: Token, close: Token) -> Result<usize> {
+    let mut depth = 0;
+    let mut end = lexer.range().end;
+    while lexer.token != Token::EndOfProgram {
+        end = lexer.range().end;
+        if lexer.token == open {
+            depth += 1;
+            if depth > MAX_DEPTH {
+                return Err(Error::NestingTooDeep {
+                    range: lexer.range(),
+                });
+            }
+        } else if lexer.token == close {
+            depth -= 1;
+            if depth == 0 {
+                break;
+            }
+        }
+        lexer.advance();
+    }
+
+    Ok(end)
+}
+
+impl<'de: 'a, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+    type Error = Error;
+
+    forward_to_deserialize_any! {
+        bytes
+        byte_buf
+        unit
+        unit_struct
+        tuple
+        map
+        identifier
+    }
+
+    deserialize_item! {
+        deserialize_any
+        deserialize_bool
+        deserialize_i8
+        deserialize_i16
+        deserialize_i32
+        deserialize_i64
+        deserialize_i128
+        deserialize_u8
+        deserialize_u16
+        deserialize_u32
+        deserialize_u64
+        deserialize_u128
+        deserialize_f32
+        deserialize_f64
+        deserialize_char
+        deserialize_str
+        deserialize_string
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<<V as Visitor<'de>>::Value>
+    where
+        V: Visitor<'de>,
+    {
+        forward_to_file!(self, deserialize_enum(name, variants, visitor));
+        forward_to_item!(self, deserialize_enum(name, variants, visitor));
+
+        self.not_a_value(&visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
+    where
+        V: Visitor<'de>,
+    {
+        forward_to_file!(self, deserialize_option(visitor));
+
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<<V as Visitor<'de>>::Value>
+    where
+        V: Visitor<'de>,
+    {
+        forward_to_file!(self, deserialize_seq(visitor));
+        forward_to_item!(self, deserialize_seq(visitor));
+
+        if self.lexer.token == Token::Identifier {
+            visitor.visit_seq(StructSeqAccess::new(self))
         } else {
             unexpected_token!(self.lexer, "[ or identifier")
         }
     }
 
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<<V as Visitor<'de>>::Value>
+    where
+        V: Visitor<'de>,
+    {
+        forward_to_file!(self, deserialize_newtype_struct(name, visitor));
+        forward_to_item!(self, deserialize_newtype_struct(name, visitor));
+
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<<V as Visitor<'de>>::Value>
+    where
+        V: Visitor<'de>,
+    {
+        forward_to_file!(self, deserialize_tuple_struct(name, len, visitor));
+        forward_to_item!(self, deserialize_tuple_struct(name, len, visitor));
+
+        self.not_a_value(&visitor)
+    }
+
     fn deserialize_struct<V>(
-        mut self,
+        self,
         type_name: &'static str,
         fields: &'static [&'static str],
         visitor: V,
@@ -402,6 +1363,34 @@ impl<'de: 'a, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
+        forward_to_file!(self, deserialize_struct(type_name, fields, visitor));
+
+        if type_name == DOCUMENTED && self.previous_end == 0 {
+            self.take_comments();
+        }
+        forward_to_item!(self, deserialize_struct(type_name, fields, visitor));
+
+        if type_name == SPANNED {
+            let span = value_span(self.lexer.clone())?;
+            return visitor.visit_map(SpannedAccess::new(self.lexer.source, span, self));
+        }
+
+        if type_name == DYNAMIC {
+            if self.lexer.token != Token::Identifier {
+                return unexpected_token!(self.lexer, "<typename>");
+            }
+            let type_name = self.lexer.slice();
+            return visitor.visit_map(DynamicAccess::new(
+                type_name,
+                DynamicSource::Streaming(self),
+            ));
+        }
+
+        if type_name == DOCUMENTED {
+            let doc = doc_text(&std::mem::take(&mut self.comments));
+            return visitor.visit_map(DocumentedAccess::new(doc, self));
+        }
+
         if !fields.contains(&"instance_name") {
             return Err(Error::MissingInstanceName);
         }
@@ -411,14 +1400,20 @@ impl<'de: 'a, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         }
 
         let lex_type_name = self.lexer.slice();
-        if lex_type_name != type_name {
+        let type_name_range = self.lexer.range();
+        if !self.options.type_names.matches(lex_type_name, type_name) {
             return Err(Error::UnexpectedStruct {
                 name: lex_type_name.to_string(),
                 expected: type_name.to_string(),
+                did_you_mean: suggestions::closest(
+                    lex_type_name,
+                    self.options.type_names.names_for(type_name),
+                ),
+                range: Some(type_name_range),
             });
         }
 
-        self.lexer.advance();
+        self.advance();
 
         let mut lex_name = None;
         match self.lexer.token {
@@ -426,7 +1421,7 @@ impl<'de: 'a, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
                 let lex_name_bit = self.lexer.slice();
                 lex_name = Some(&lex_name_bit[1..][..lex_name_bit.len() - 2]);
 
-                self.lexer.advance();
+                self.advance();
             }
             Token::BraceOpen => {}
             _ => {
@@ -438,18 +1433,642 @@ impl<'de: 'a, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
             return unexpected_token!(self.lexer, "{");
         }
 
-        self.lexer.advance();
+        self.advance();
 
-        visitor.visit_map(StructInternalAccess::new(&mut self, lex_name.unwrap_or("")))
+        if let Some(name) = lex_name {
+            self.options.references.add_instance(type_name, name);
+        }
+
+        self.nested(|de| {
+            visitor.visit_map(StructInternalAccess::new(
+                de,
+                lex_name.unwrap_or(""),
+                fields,
+            ))
+        })
+        .map_err(|error| error.at(&type_name_range))
     }
 
     fn deserialize_ignored_any<V>(
         self,
-        _visitor: V,
+        visitor: V,
     ) -> Result<<V as Visitor<'de>>::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        unimplemented!("Ignoring items currently not supported.")
+        forward_to_file!(self, deserialize_ignored_any(visitor));
+
+        if self.lexer.token != Token::Identifier {
+            self.skip_value()?;
+            return visitor.visit_unit();
+        }
+
+        // A flag or a struct block
+        self.advance();
+        while self.lexer.token == Token::Text {
+            self.advance();
+        }
+        if self.lexer.token == Token::BraceOpen {
+            let mut depth = 0;
+            let mut nesting = self.depth;
+            loop {
+                match self.lexer.token {
+                    Token::BraceOpen | Token::BracketOpen if nesting == MAX_DEPTH => {
+                        return Err(Error::NestingTooDeep {
+                            range: self.lexer.range(),
+                        });
+                    }
+                    Token::BraceOpen => {
+                        depth += 1;
+                        nesting += 1;
+                    }
+                    Token::BracketOpen => nesting += 1,
+                    Token::BraceClose => {
+                        depth -= 1;
+                        nesting = nesting.saturating_sub(1);
+                    }
+                    Token::BracketClose => nesting = nesting.saturating_sub(1),
+                    Token::EndOfProgram => return unexpected_token!(self.lexer, "}"),
+                    _ => {}
+                }
+                self.advance();
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+        visitor.visit_unit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Deserializer, Documented, Error, ErrorKind, Spanned, TypeNameMatching};
+    use crate::ast::{self, ListValue, Value, ValueKind};
+    use crate::fixtures::{ItemType, Monster};
+    use serde::Deserialize;
+    use serde_derive::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename = "shop")]
+    struct Shop {
+        instance_name: String,
+        item_type: Vec<ItemType>,
+    }
+
+    #[test]
+    fn duplicate_property() {
+        let result = Deserializer::from_str::<Monster>("monster \"Orc\" { hp = 10 hp = 12 }");
+
+        match result {
+            Err(Error::DuplicateProperty {
+                name,
+                first,
+                second,
+            }) => {
+                assert_eq!(name, "hp");
+                assert_eq!(first, 16..18);
+                assert_eq!(second, 24..26);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn duplicate_property_last_wins() {
+        let mut de = Deserializer::new("monster \"Orc\" { hp = 10 undead hp = 12 undead }")
+            .last_property_wins(true);
+        let monster = Monster::deserialize(&mut de).unwrap();
+
+        assert_eq!(monster.instance_name, "Orc");
+        assert_eq!(monster.hp, 12);
+        assert!(monster.undead);
+    }
+
+    #[test]
+    fn type_name_alias() {
+        let mut de = Deserializer::new(
+            "shop { item_type \"Sword\" { cost = 10 } ItemType \"Bow\" { cost = 5 } }",
+        )
+        .type_name_alias("item_type", "ItemType");
+        let shop = Shop::deserialize(&mut de).unwrap();
+
+        assert_eq!(shop.instance_name, "");
+        assert_eq!(shop.item_type.len(), 2);
+        assert_eq!(shop.item_type[1].instance_name, "Bow");
+        assert_eq!(shop.item_type[1].cost, 5);
+    }
+
+    #[test]
+    fn type_name_matching() {
+        let source = "ItemType \"Sword\" { cost = 10 }";
+
+        let result = Deserializer::from_str::<ItemType>(source);
+        assert!(matches!(result, Err(Error::UnexpectedStruct { .. })));
+
+        let mut de = Deserializer::new(source).type_name_matching(TypeNameMatching::IgnoreCase);
+        assert!(ItemType::deserialize(&mut de).is_err());
+
+        let mut de = Deserializer::new(source)
+            .type_name_matching(TypeNameMatching::IgnoreCaseAndUnderscores);
+        assert_eq!(ItemType::deserialize(&mut de).unwrap().cost, 10);
+    }
+
+    #[test]
+    fn did_you_mean() {
+        let result = Deserializer::from_str::<Monster>("monstr \"Orc\" { hp = 10 }");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Found struct monstr at position 0..6, expected struct monster, did you mean `monster`?"
+        );
+
+        let result = Deserializer::from_str::<Monster>("monster \"Orc\" { hpp = 10 }");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Found unknown field hpp at position 16..19, did you mean `hp`?"
+        );
+    }
+
+    #[test]
+    fn inherited_errors() {
+        let source = r#"monster "Chief" { inherits = "Orc" undead }
+monster "Orc" { inherits = "Base" }
+monster "Base" { hp = "lots" }"#;
+        let check = |mut de: Deserializer| {
+            let error = Vec::<Monster>::deserialize(&mut de).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidType);
+            assert_eq!(error.range(), Some(102..108));
+            match error {
+                Error::Inherited { chain, block, .. } => {
+                    assert_eq!(chain, ["Chief", "Orc", "Base"]);
+                    assert_eq!(block, 0..7);
+                }
+                error => panic!("unexpected error: {:?}", error),
+            }
+        };
+
+        check(Deserializer::new(source).inheritance("inherits"));
+        check(Deserializer::from_file(ast::parse(source).unwrap()).inheritance("inherits"));
+    }
+
+    #[test]
+    fn lexical_error() {
+        let result = Deserializer::from_str::<Monster>("monster \"Orc\" {\n  hp = 10 /* }\n");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid token at position 26..28: unclosed comment"
+        );
+    }
+
+    #[test]
+    fn unknown_fields_and_bad_numbers() {
+        let source = "item_type \"Sword\" {\n  heft = [1, 2.5]\n  sharp\n  enchantment { x = 'a' }\n  cost = 10\n}";
+        let sword = Deserializer::from_str::<ItemType>(source).unwrap();
+        assert_eq!(sword.cost, 10);
+
+        let source = "item_type \"Sword\" { cost = 99999999999 }";
+        assert!(matches!(
+            Deserializer::from_str::<ItemType>(source),
+            Err(Error::InvalidValue { range, .. }) if range == Some(27..38)
+        ));
+    }
+
+    #[test]
+    fn streaming_and_syntax_tree_agree() {
+        #[allow(dead_code)]
+        #[derive(Debug, Deserialize)]
+        struct Hp(i32);
+
+        #[derive(Debug, Deserialize)]
+        #[serde(rename_all = "lowercase")]
+        enum Slot {
+            Hand,
+        }
+
+        #[allow(dead_code)]
+        #[derive(Debug, Deserialize)]
+        #[serde(rename = "effect")]
+        struct Effect {
+            instance_name: String,
+            #[serde(default)]
+            power: Option<f32>,
+            #[serde(default)]
+            hp: Option<Hp>,
+            #[serde(default)]
+            cost: Option<u8>,
+            #[serde(default)]
+            symbol: Option<char>,
+            #[serde(default)]
+            name: Option<String>,
+            #[serde(default)]
+            label: Option<&'static str>,
+            #[serde(default)]
+            slot: Option<Slot>,
+            #[serde(default)]
+            color: Option<(u8, u8, u8)>,
+            #[serde(default)]
+            pair: Option<(i32, i32)>,
+            #[serde(default)]
+            list: Option<Vec<Vec<i64>>>,
+            #[serde(default)]
+            flag: Option<bool>,
+        }
+
+        let sources = [
+            "effect { power = 1 }",
+            "effect { power = 0x10 }",
+            "effect { hp = 5 }",
+            "effect { hp = 2.5 }",
+            "effect { cost = 300 }",
+            "effect { cost = -1 }",
+            "effect { cost = 99999999999 }",
+            "effect { symbol = 65 }",
+            "effect { symbol = 'a' }",
+            "effect { symbol = \"a\" }",
+            "effect { name = \"a\" \"b\" }",
+            "effect { name = 1 }",
+            "effect { label = \"a\" \"b\" }",
+            "effect { slot = \"hand\" }",
+            "effect { slot = \"head\" }",
+            "effect { slot = 1 }",
+            "effect { color = #FF0080 }",
+            "effect { color = [1, 2, 3] }",
+            "effect { pair = [1, 2, 3] }",
+            "effect { pair = [1] }",
+            "effect { list = [[1], [], [2, 3]] }",
+            "effect { list = [1] }",
+            "effect { flag }",
+            "effect { flag = 1 }",
+            "effect { power }",
+            "effect { power { } }",
+            "effect { name { } }",
+        ];
+        for source in sources {
+            let streamed = Deserializer::from_str::<Effect>(source);
+            let mut de = Deserializer::from_file(ast::parse(source).unwrap());
+            let parsed = Effect::deserialize(&mut de);
+            assert_eq!(
+                format!("{:?}", streamed),
+                format!("{:?}", parsed),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn unit_enum_values() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(rename_all = "lowercase")]
+        enum Slot {
+            Hand,
+            Head,
+        }
+
+        #[derive(Debug, Deserialize)]
+        #[serde(rename = "item_type")]
+        struct Equipment {
+            instance_name: String,
+            slot: Slot,
+        }
+
+        let source = "item_type \"Helmet\" { slot = \"head\" }";
+        let helmet = Deserializer::from_str::<Equipment>(source).unwrap();
+        assert_eq!(helmet.instance_name, "Helmet");
+        assert_eq!(helmet.slot, Slot::Head);
+        let mut de = Deserializer::from_file(ast::parse(source).unwrap());
+        assert_eq!(Equipment::deserialize(&mut de).unwrap().slot, Slot::Head);
+
+        let source = "item_type \"Boot\" { slot = \"hend\" }";
+        let check = |error: Error| match error {
+            Error::UnknownVariant {
+                name,
+                expected,
+                did_you_mean,
+                range,
+            } => {
+                assert_eq!(name, "hend");
+                assert_eq!(expected, ["hand", "head"]);
+                assert_eq!(did_you_mean, ["hand", "head"]);
+                assert_eq!(range, Some(26..32));
+            }
+            error => panic!("unexpected error: {:?}", error),
+        };
+        check(Deserializer::from_str::<Equipment>(source).unwrap_err());
+        let mut de = Deserializer::from_file(ast::parse(source).unwrap());
+        check(Equipment::deserialize(&mut de).unwrap_err());
+    }
+
+    #[test]
+    fn custom_types() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Dice {
+            count: i64,
+            sides: i64,
+        }
+
+        #[derive(Debug, Deserialize)]
+        #[serde(rename = "weapon")]
+        struct Weapon {
+            instance_name: String,
+            #[serde(default)]
+            bonus: Option<Dice>,
+            damage: Vec<Dice>,
+            cost: i32,
+            #[serde(default)]
+            sharp: bool,
+        }
+
+        fn dice(de: Deserializer<'_>) -> Deserializer<'_> {
+            de.custom_type("Dice", |raw| {
+                let (count, sides) = raw.trim_matches('"').split_once('d').ok_or("dice")?;
+                let number = |n: &str| n.parse().map_err(|_| "dice".to_string());
+                let value = |n| Value {
+                    kind: ValueKind::Integer(n),
+                    span: 0..0,
+                };
+                Ok(ValueKind::List(ListValue {
+                    values: vec![value(number(count)?), value(number(sides)?)],
+                    span: 0..0,
+                }))
+            })
+        }
+
+        let source = "weapon { damage = [1d4, 2d6 ] cost = 3 }";
+        let weapon = Weapon::deserialize(&mut dice(Deserializer::new(source))).unwrap();
+        assert_eq!(weapon.damage[1], Dice { count: 2, sides: 6 });
+        assert_eq!((weapon.instance_name.as_str(), weapon.cost), ("", 3));
+
+        let source = "weapon { bonus = 1d6 sharp damage = [] cost = 3 }";
+        let weapon = Weapon::deserialize(&mut dice(Deserializer::new(source))).unwrap();
+        assert_eq!(weapon.bonus, Some(Dice { count: 1, sides: 6 }));
+        assert_eq!((weapon.sharp, weapon.cost), (true, 3));
+
+        let source = "weapon { bonus = \"1d6\" damage = [\"1d4\"] cost = 3 }";
+        let mut de = dice(Deserializer::from_file(ast::parse(source).unwrap()));
+        let weapon = Weapon::deserialize(&mut de).unwrap();
+        assert_eq!((weapon.bonus.is_some(), weapon.damage.len()), (true, 1));
+
+        let source = "weapon { damage = [1d4, 2x6] cost = 3 }";
+        assert!(matches!(
+            Weapon::deserialize(&mut dice(Deserializer::new(source))),
+            Err(Error::InvalidValue { unexpected, range, .. })
+                if unexpected == "`2x6`" && range == Some(24..27)
+        ));
+    }
+
+    #[test]
+    fn from_file() {
+        let source = "shop \"Smith\" {\n  item_type \"Sword\" { cost = 10 }\n  ItemType \"Bow\" { cost = 5 }\n}";
+        let mut de = Deserializer::from_file(ast::parse(source).unwrap())
+            .type_name_alias("item_type", "ItemType");
+        let shop = Shop::deserialize(&mut de).unwrap();
+
+        assert_eq!(shop.instance_name, "Smith");
+        assert_eq!(shop.item_type.len(), 2);
+        assert_eq!(shop.item_type[0].instance_name, "Sword");
+        assert_eq!(shop.item_type[1].cost, 5);
+
+        let source = "monster \"Orc\" { hp = 10 undead hp = 12 }";
+        let mut de = Deserializer::from_file(ast::parse(source).unwrap());
+        assert!(matches!(
+            Monster::deserialize(&mut de),
+            Err(Error::DuplicateProperty { .. })
+        ));
+
+        let mut de = Deserializer::from_file(ast::parse(source).unwrap()).last_property_wins(true);
+        let monster = Monster::deserialize(&mut de).unwrap();
+        assert_eq!(monster.hp, 12);
+        assert!(monster.undead);
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename = "monster")]
+    struct DocumentedMonster {
+        instance_name: String,
+        hp: Documented<i32>,
+        undead: Documented<bool>,
+        item_type: Vec<Documented<ItemType>>,
+    }
+
+    #[test]
+    fn documented() {
+        let source = "// Greenskins.
+monster \"Orc\" {
+    // Hit points.
+    hp = 10 // Not documentation.
+    /* Rises again. */
+    undead
+
+    // Cheap.
+    item_type \"Club\" { cost = 1 }
+    item_type \"Axe\" { cost = 5 }
+}";
+        let check = |monster: Documented<DocumentedMonster>| {
+            assert_eq!(monster.doc.as_deref(), Some("Greenskins."));
+            assert_eq!(monster.instance_name, "Orc");
+            assert_eq!(*monster.hp, 10);
+            assert_eq!(monster.hp.doc.as_deref(), Some("Hit points."));
+            assert!(*monster.undead);
+            assert_eq!(monster.undead.doc.as_deref(), Some("Rises again."));
+            assert_eq!(monster.item_type[0].doc.as_deref(), Some("Cheap."));
+            assert_eq!(monster.item_type[1].cost, 5);
+            assert_eq!(monster.item_type[1].doc, None);
+        };
+
+        check(Deserializer::from_str(source).unwrap());
+        let mut de = Deserializer::from_file(ast::parse(source).unwrap());
+        check(Documented::deserialize(&mut de).unwrap());
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename = "shop")]
+    struct SpannedShop {
+        instance_name: String,
+        prices: Spanned<Vec<Spanned<i32>>>,
+        open: Spanned<bool>,
+        item_type: Vec<Spanned<ItemType>>,
+    }
+
+    #[test]
+    fn spanned() {
+        let source =
+            "shop \"Smith\" {\n  prices = [1, 20]\n  open\n  item_type \"Sword\" { cost = 10 }\n}";
+        let check = |shop: Spanned<SpannedShop>| {
+            assert_eq!(shop.span, 0..source.len());
+            assert_eq!(shop.instance_name, "Smith");
+            assert_eq!(&source[shop.prices.span.clone()], "[1, 20]");
+            assert_eq!((shop.prices.start.line, shop.prices.start.column), (2, 12));
+            assert_eq!((shop.prices.end.line, shop.prices.end.column), (2, 19));
+            assert_eq!(*shop.prices[1], 20);
+            assert_eq!(&source[shop.prices[1].span.clone()], "20");
+            assert_eq!(&source[shop.open.span.clone()], "open");
+            assert_eq!(shop.open.start.line, 3);
+            assert_eq!(
+                &source[shop.item_type[0].span.clone()],
+                "item_type \"Sword\" { cost = 10 }"
+            );
+            assert_eq!(shop.item_type[0].cost, 10);
+        };
+
+        check(Deserializer::from_str(source).unwrap());
+        let mut de = Deserializer::from_file(ast::parse(source).unwrap());
+        check(Spanned::deserialize(&mut de).unwrap());
+    }
+
+    #[test]
+    fn recovering() {
+        let source = "monster \"Orc\" { hp = \"lots\" undead }
+monster \"Goblin\" { hp = 5 }
+monster \"Troll\" { hp = 8 hp = 9 undead }
+monster \"Elf\" { hp = }
+monster \"Ghost\" { undead }";
+        let recovered = Deserializer::from_str_recovering::<Vec<Monster>>(source);
+
+        let errors = &recovered.errors;
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert!(matches!(errors[0], Error::UnexpectedToken { .. }));
+        assert!(matches!(errors[1], Error::InvalidType { .. }));
+        assert!(matches!(errors[2], Error::DuplicateProperty { .. }));
+        assert!(matches!(errors[3], Error::MissingField { name: "hp", .. }));
+
+        let monsters = recovered.value.unwrap();
+        let names = monsters
+            .iter()
+            .map(|monster| monster.instance_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Goblin", "Troll"]);
+        assert_eq!(monsters[1].hp, 8);
+        assert!(monsters[1].undead);
+
+        let recovered = Deserializer::from_str_recovering::<Monster>("monster { hp = 1 }");
+        assert!(recovered.into_result().is_ok());
+
+        let recovered = Deserializer::from_str_recovering::<Monster>("monster { hp = \"1\" }");
+        assert_eq!(recovered.errors.len(), 1, "{:?}", recovered.errors);
+    }
+
+    #[test]
+    fn recovering_keeps_unrelated_errors() {
+        #[allow(dead_code)]
+        #[derive(Debug)]
+        struct Roster(Vec<Monster>);
+
+        impl<'de> Deserialize<'de> for Roster {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let monsters = Vec::<Monster>::deserialize(deserializer)?;
+                match monsters.len() {
+                    1 => Err(serde::de::Error::custom("a roster needs two monsters")),
+                    _ => Ok(Roster(monsters)),
+                }
+            }
+        }
+
+        let source = "monster \"Orc\" { hp = \"lots\" }\nmonster \"Goblin\" { hp = 5 }";
+        let recovered = Deserializer::from_str_recovering::<Roster>(source);
+
+        let errors = &recovered.errors;
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(matches!(errors[0], Error::InvalidType { .. }));
+        assert!(matches!(errors[1], Error::Serde { .. }));
+        assert!(recovered.value.is_none());
+    }
+
+    #[test]
+    fn error_kinds() {
+        let from_file = |source| {
+            let file = ast::parse(source)?;
+            Monster::deserialize(&mut Deserializer::from_file(file))
+        };
+        for deserialize in [Deserializer::from_str, from_file] {
+            let kind = |source| match deserialize(source) {
+                Err(error) => (error.kind(), error.code(), error.range()),
+                Ok(monster) => panic!("unexpected monster: {:?}", monster),
+            };
+
+            assert_eq!(
+                kind("monster \"Orc\" { hp = \"lots\" }"),
+                (ErrorKind::InvalidType, "TCOD0014", Some(21..27))
+            );
+            assert_eq!(
+                kind("monster { hp { } }"),
+                (ErrorKind::InvalidType, "TCOD0014", Some(10..16))
+            );
+            assert_eq!(
+                kind("monster { }"),
+                (ErrorKind::MissingField, "TCOD0008", Some(0..7))
+            );
+            assert_eq!(
+                kind("monster { hp = 1 legs = 2 }"),
+                (ErrorKind::UnknownField, "TCOD0007", Some(17..21))
+            );
+            assert_eq!(
+                kind("monster { hp = }"),
+                (ErrorKind::UnexpectedToken, "TCOD0002", Some(15..16))
+            );
+        }
+
+        let file = ast::parse("item_type { cost = 300 }").unwrap();
+        match Deserializer::from_file(file)
+            .deserialize_recovering::<SmallItemType>()
+            .errors[..]
+        {
+            [Error::InvalidValue {
+                ref unexpected,
+                ref expected,
+                ..
+            }] => {
+                assert_eq!(unexpected, "integer `300`");
+                assert_eq!(expected, "u8");
+            }
+            ref errors => panic!("unexpected errors: {:?}", errors),
+        }
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize)]
+    #[serde(rename = "item_type")]
+    struct SmallItemType {
+        instance_name: String,
+        cost: u8,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    struct Positive(i32);
+
+    impl<'de> Deserialize<'de> for Positive {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            match i32::deserialize(deserializer)? {
+                value if value > 0 => Ok(Positive(value)),
+                _ => Err(serde::de::Error::custom("expected a positive number")),
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize)]
+    #[serde(rename = "monster")]
+    struct PositiveMonster {
+        instance_name: String,
+        hp: Positive,
+        resistances: Vec<Positive>,
+    }
+
+    #[test]
+    fn custom_error_position() {
+        let source = "monster \"Orc\" { hp = 5 resistances = [1, -2] }";
+        let check = |error: Error| {
+            assert!(matches!(error, Error::Serde { .. }));
+            assert_eq!(error.range(), Some(41..43));
+            assert!(error.to_string().contains("at position 41..43"));
+        };
+
+        check(Deserializer::from_str::<PositiveMonster>(source).unwrap_err());
+        let mut de = Deserializer::from_file(ast::parse(source).unwrap());
+        check(PositiveMonster::deserialize(&mut de).unwrap_err());
+
+        let error = Deserializer::from_str::<Monster>("monster { }").unwrap_err();
+        assert_eq!(error.range(), Some(0..7));
     }
 }
@@ -0,0 +1,154 @@
+use serde::de::{self, Unexpected};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+/// The allowed values of a [`OneOf`].
+///
+/// [`OneOf`]: struct.OneOf.html
+pub trait ValueList {
+    /// The values a [`OneOf`] accepts.
+    ///
+    /// [`OneOf`]: struct.OneOf.html
+    const VALUES: &'static [&'static str];
+}
+
+/// A string that must be one of the values of `L`, like a property declared with libtcod's
+/// `addValueList`. Any other value is rejected with an [`Error::InvalidValue`] that lists the
+/// allowed values.
+///
+/// When the values should be mapped to something else, deserializing into an enum with unit
+/// variants does the same, rejecting unknown values with an [`Error::UnknownVariant`].
+///
+/// ```
+/// use serde_derive::Deserialize;
+/// use serde_tcod_config_parser::de::{Deserializer, OneOf, ValueList};
+///
+/// struct Slots;
+///
+/// impl ValueList for Slots {
+///     const VALUES: &'static [&'static str] = &["hand", "head", "body"];
+/// }
+///
+/// #[derive(Deserialize)]
+/// #[serde(rename = "item_type")]
+/// struct ItemType {
+///     instance_name: String,
+///     slot: OneOf<Slots>,
+/// }
+///
+/// let sword: ItemType = Deserializer::from_str(r#"item_type "Sword" { slot = "hand" }"#).unwrap();
+/// assert_eq!(&*sword.slot, "hand");
+/// assert_eq!(sword.slot.index(), 0);
+///
+/// let boot = Deserializer::from_str::<ItemType>(r#"item_type "Boot" { slot = "foot" }"#);
+/// assert!(boot.is_err());
+/// ```
+///
+/// [`Error::InvalidValue`]: enum.Error.html#variant.InvalidValue
+/// [`Error::UnknownVariant`]: enum.Error.html#variant.UnknownVariant
+pub struct OneOf<L> {
+    value: String,
+    list: PhantomData<fn() -> L>,
+}
+
+impl<L: ValueList> OneOf<L> {
+    /// The value, if it is one of the values of `L`.
+    pub fn new(value: impl Into<String>) -> Option<Self> {
+        let value = value.into();
+        if L::VALUES.contains(&value.as_str()) {
+            Some(Self {
+                value,
+                list: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The position of the value in `L::VALUES`.
+    pub fn index(&self) -> usize {
+        L::VALUES
+            .iter()
+            .position(|value| *value == self.value)
+            .unwrap_or_default()
+    }
+}
+
+impl<L> OneOf<L> {
+    /// The value as a `&str`.
+    pub fn as_str(&self) -> &str {
+        &self.value
+    }
+
+    /// The value as a `String`.
+    pub fn into_inner(self) -> String {
+        self.value
+    }
+}
+
+impl<L> Deref for OneOf<L> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.value
+    }
+}
+
+impl<L> fmt::Debug for OneOf<L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OneOf").field(&self.value).finish()
+    }
+}
+
+impl<L> Clone for OneOf<L> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            list: PhantomData,
+        }
+    }
+}
+
+impl<L> PartialEq for OneOf<L> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<L> Eq for OneOf<L> {}
+
+/// Describes the values of a value list, for errors.
+struct Expected(&'static [&'static str]);
+
+impl de::Expected for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("one of ")?;
+        for (index, value) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "\"{}\"", value)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'de, L: ValueList> de::Deserialize<'de> for OneOf<L> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let value = <String as de::Deserialize>::deserialize(deserializer)?;
+        if !L::VALUES.contains(&value.as_str()) {
+            return Err(de::Error::invalid_value(
+                Unexpected::Str(&value),
+                &Expected(L::VALUES),
+            ));
+        }
+        Ok(Self {
+            value,
+            list: PhantomData,
+        })
+    }
+}
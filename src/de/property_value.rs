@@ -0,0 +1,219 @@
+use crate::ast::{doc_text, Value};
+use crate::de::{
+    at_value_span, value_span, Deserializer, DocumentedAccess, Error, Result, SpannedAccess,
+    ValueDeserializer, DOCUMENTED, REF_FIELDS, SPANNED,
+};
+use crate::lexer::Token;
+use serde::de::{self, Visitor};
+use serde::forward_to_deserialize_any;
+
+/// Deserializes the property value or list element at the current token of a [`Deserializer`]
+/// exactly like a [`ValueDeserializer`] deserializes it from the syntax tree, by parsing it and
+/// handing it to one. Lists are walked element by element instead, so that their elements can be
+/// values of custom types, which don't necessarily parse as regular values.
+pub struct PropertyValueDeserializer<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+}
+
+/// What the value at the current token turned out to be.
+enum Next<'de> {
+    List,
+    Value(Value<'de>),
+}
+
+impl<'a, 'de> PropertyValueDeserializer<'a, 'de> {
+    pub fn new(de: &'a mut Deserializer<'de>) -> Self {
+        Self { de }
+    }
+
+    /// Parses the value at the current token, unless it is a list.
+    fn next(&mut self) -> Result<Next<'de>> {
+        if self.de.lexer.token == Token::BracketOpen {
+            Ok(Next::List)
+        } else {
+            self.de.parse_value().map(Next::Value)
+        }
+    }
+
+    /// Visits the elements of the list at the current token, and moves on past it.
+    fn visit_list<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.de.nested(|de| {
+            de.comments.clear();
+            de.advance();
+            let result = visitor.visit_seq(ListAccess { de: &mut *de })?;
+
+            // Skip the elements the visitor didn't ask for, like it ignores the rest of a parsed
+            // list
+            while de.lexer.token != Token::BracketClose {
+                de.skip_value()?;
+                end_element(de)?;
+            }
+            de.advance();
+
+            Ok(result)
+        })
+    }
+}
+
+/// Moves on past the `,` after a list element, if the list doesn't end with it.
+fn end_element(de: &mut Deserializer<'_>) -> Result {
+    match de.lexer.token {
+        Token::Comma => de.advance(),
+        Token::BracketClose => {}
+        _ => return unexpected_token!(de.lexer, "<value> or ]"),
+    }
+    Ok(())
+}
+
+macro_rules! visit_next {
+    ($self: ident, $method: ident($($arg: expr),*), $visitor: ident) => {
+        match $self.next()? {
+            Next::List => $self.visit_list($visitor),
+            Next::Value(value) => ValueDeserializer::new(&value, $self.de.lexer.source)
+                .options(&$self.de.options)
+                .$method($($arg,)* $visitor),
+        }
+    };
+}
+
+impl<'de: 'a, 'a> de::Deserializer<'de> for PropertyValueDeserializer<'a, 'de> {
+    type Error = Error;
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 string
+        bytes byte_buf unit unit_struct seq tuple map identifier
+    }
+
+    fn deserialize_any<V>(mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visit_next!(self, deserialize_any(), visitor)
+    }
+
+    fn deserialize_char<V>(mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visit_next!(self, deserialize_char(), visitor)
+    }
+
+    fn deserialize_str<V>(mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visit_next!(self, deserialize_str(), visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        forward_to_custom_type!(self.de, name, deserialize_newtype_struct(name, visitor));
+
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        mut self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        forward_to_custom_type!(self.de, name, deserialize_tuple_struct(name, len, visitor));
+
+        visit_next!(self, deserialize_tuple_struct(name, len), visitor)
+    }
+
+    fn deserialize_enum<V>(
+        mut self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        forward_to_custom_type!(self.de, name, deserialize_enum(name, variants, visitor));
+
+        visit_next!(self, deserialize_enum(name, variants), visitor)
+    }
+
+    fn deserialize_struct<V>(
+        mut self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if fields == REF_FIELDS {
+            let value = self.de.parse_value()?;
+            return ValueDeserializer::new(&value, self.de.lexer.source)
+                .options(&self.de.options)
+                .deserialize_struct(name, fields, visitor);
+        }
+
+        forward_to_custom_type!(self.de, name, deserialize_struct(name, fields, visitor));
+
+        if name == SPANNED {
+            let span = value_span(self.de.lexer.clone())?;
+            let source = self.de.lexer.source;
+            return visitor.visit_map(SpannedAccess::new(source, span, self));
+        }
+
+        if name == DOCUMENTED {
+            let doc = doc_text(&std::mem::take(&mut self.de.comments));
+            return visitor.visit_map(DocumentedAccess::new(doc, self));
+        }
+
+        visit_next!(self, deserialize_struct(name, fields), visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.de.skip_value()?;
+        visitor.visit_unit()
+    }
+}
+
+struct ListAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de: 'a, 'a> de::SeqAccess<'de> for ListAccess<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        if self.de.lexer.token == Token::BracketClose {
+            return Ok(None);
+        }
+
+        let value = self.de.lexer.clone();
+        let result = seed
+            .deserialize(PropertyValueDeserializer::new(&mut *self.de))
+            .map_err(|error| at_value_span(error, value))?;
+        end_element(self.de)?;
+
+        Ok(Some(result))
+    }
+}
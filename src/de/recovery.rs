@@ -0,0 +1,155 @@
+use crate::ast::{self, Item, StructBlock};
+use crate::de::{Deserializer, Error, StructsDeserializer};
+use crate::lexer::Span;
+use serde::de;
+use std::cell::Cell;
+
+/// The result of deserializing while recovering from errors.
+#[derive(Debug)]
+pub struct Recovered<T> {
+    /// The deserialized value, with the struct blocks, properties and flags that failed to
+    /// deserialize left out, or `None` if not even that was possible.
+    pub value: Option<T>,
+    /// All the errors encountered, in the order they were found.
+    pub errors: Vec<Error>,
+}
+
+impl<T> Recovered<T> {
+    /// The value if there were no errors, or all the errors otherwise.
+    pub fn into_result(self) -> Result<T, Vec<Error>> {
+        match self.value {
+            Some(value) if self.errors.is_empty() => Ok(value),
+            _ => Err(self.errors),
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> {
+    /// Deserializes a value, recovering from errors instead of stopping at the first one.
+    ///
+    /// Syntax errors are recovered from by skipping ahead to the next property or the end of the
+    /// struct block. A struct block, property or flag that fails to deserialize is left out, and
+    /// the rest is deserialized again without it, so if a value is returned, it is missing the
+    /// failed elements, e.g. a `Vec` of structs leaves out the broken ones. Errors that only
+    /// happened because of an earlier one, like a missing field after its value was left out, are
+    /// not reported.
+    ///
+    /// As every element that is left out means deserializing the whole file again, this takes time
+    /// proportional to the size of the file times the number of broken elements.
+    ///
+    /// ```
+    /// use serde_derive::Deserialize;
+    /// use serde_tcod_config_parser::de::Deserializer;
+    ///
+    /// #[derive(Deserialize)]
+    /// #[serde(rename = "monster")]
+    /// struct Monster {
+    ///     instance_name: String,
+    ///     hp: i32,
+    /// }
+    ///
+    /// let source = r#"
+    ///     monster "Orc" { hp = "lots" }
+    ///     monster "Goblin" { hp = 5 }
+    ///     monster "Elf" { hp = }
+    /// "#;
+    /// let recovered = Deserializer::from_str_recovering::<Vec<Monster>>(source);
+    ///
+    /// assert_eq!(recovered.errors.len(), 2);
+    /// assert_eq!(recovered.value.unwrap().len(), 1);
+    /// ```
+    pub fn deserialize_recovering<T: de::Deserialize<'de>>(mut self) -> Recovered<T> {
+        let (mut file, mut errors) = match self.file.take() {
+            Some(file) => (file, Vec::new()),
+            None => ast::parse_recovering(self.lexer.source),
+        };
+        if let Some(property) = self.options.inheritance.take() {
+            if let Err(error) = ast::resolve_inheritance(&mut file, &property) {
+                errors.push(error);
+            }
+        }
+        let mut broken = errors.iter().filter_map(Error::range).collect::<Vec<_>>();
+
+        loop {
+            self.options.failed_item.replace(None);
+            let consumed = Cell::new(0);
+            let blocks = file.structs.iter().collect();
+            let error = match T::deserialize(StructsDeserializer::new(
+                blocks,
+                file.source,
+                &self.options,
+                &consumed,
+            )) {
+                Ok(value) => {
+                    return Recovered {
+                        value: Some(value),
+                        errors,
+                    }
+                }
+                Err(error) => error,
+            };
+
+            match self.options.failed_item.replace(None) {
+                Some(span) if remove(&mut file.structs, &span) => {
+                    if !is_consequence(&error, &span, &broken) {
+                        errors.push(error);
+                    }
+                    broken.push(span);
+                }
+                _ => {
+                    if !is_consequence(&error, &(0..file.source.len()), &broken) {
+                        errors.push(error);
+                    }
+                    return Recovered {
+                        value: None,
+                        errors,
+                    };
+                }
+            }
+        }
+    }
+
+    /// Deserializes a value from a `&str`, recovering from errors instead of stopping at the
+    /// first one. See [`deserialize_recovering`] for details.
+    ///
+    /// [`deserialize_recovering`]: #method.deserialize_recovering
+    pub fn from_str_recovering<T: de::Deserialize<'de>>(s: &'de str) -> Recovered<T> {
+        Deserializer::new(s).deserialize_recovering()
+    }
+}
+
+/// Whether `error`, which made the item at `span` fail, is just a consequence of an earlier error
+/// within that item, e.g. a field that is missing because its value was broken, or a struct
+/// missing because its struct block was left out.
+fn is_consequence(error: &Error, span: &Span, broken: &[Span]) -> bool {
+    match error {
+        Error::MissingField { .. } | Error::InvalidLength { .. } => broken
+            .iter()
+            .any(|broken| span.start <= broken.start && broken.end <= span.end),
+        _ => false,
+    }
+}
+
+/// Removes the struct block, property or flag at `span`. Returns whether there was one.
+fn remove(blocks: &mut Vec<StructBlock<'_>>, span: &Span) -> bool {
+    if let Some(index) = blocks.iter().position(|block| &block.span == span) {
+        blocks.remove(index);
+        return true;
+    }
+
+    blocks
+        .iter_mut()
+        .any(|block| remove_item(&mut block.items, span))
+}
+
+fn remove_item(items: &mut Vec<Item<'_>>, span: &Span) -> bool {
+    if let Some(index) = items.iter().position(|item| item.span() == span) {
+        items.remove(index);
+        return true;
+    }
+
+    items.iter_mut().any(|item| match item {
+        Item::Struct(block) => remove_item(&mut block.items, span),
+        _ => false,
+    })
+}
@@ -0,0 +1,278 @@
+use crate::de::spanned::SpannedVisitor;
+use crate::de::{suggestions, Error, Spanned, REF_FIELDS};
+use crate::lexer::Span;
+use serde::de::{self, Visitor};
+use serde::forward_to_deserialize_any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A reference to another struct block by its instance name, e.g. `ammo = "Arrow"` pointing at
+/// `item_type "Arrow" { ... }`, where `T` is the type of the struct block pointed at.
+///
+/// References are checked once everything has been deserialized, with
+/// [`Deserializer::check_references`], which reports every reference to an instance name that
+/// no struct block of the right type has. The target can then be looked up with [`resolve`].
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_derive::Deserialize;
+/// use serde_tcod_config_parser::ast;
+/// use serde_tcod_config_parser::de::{Deserializer, Instance, Ref};
+///
+/// #[derive(Deserialize)]
+/// #[serde(rename = "item_type")]
+/// struct ItemType {
+///     instance_name: String,
+///     ammo: Option<Ref<ItemType>>,
+/// }
+///
+/// impl Instance for ItemType {
+///     fn instance_name(&self) -> &str {
+///         &self.instance_name
+///     }
+/// }
+///
+/// let source = r#"item_type "Bow" { ammo = "Arrow" } item_type "Arrow" { }"#;
+/// let mut de = Deserializer::from_file(ast::parse(source).unwrap());
+/// let items = Vec::<ItemType>::deserialize(&mut de).unwrap();
+/// de.check_references().unwrap();
+///
+/// let ammo = items[0].ammo.as_ref().unwrap();
+/// assert_eq!(ammo.resolve(&items).unwrap().instance_name, "Arrow");
+///
+/// let mut de = Deserializer::new(r#"item_type "Bow" { ammo = "Arow" }"#);
+/// ItemType::deserialize(&mut de).unwrap();
+/// assert!(de.check_references().is_err());
+/// ```
+///
+/// [`Deserializer::check_references`]: struct.Deserializer.html#method.check_references
+/// [`resolve`]: #method.resolve
+pub struct Ref<T> {
+    name: Spanned<String>,
+    target: PhantomData<fn() -> T>,
+}
+
+/// A struct type whose instances [`Ref`]s can be resolved to.
+///
+/// [`Ref`]: struct.Ref.html
+pub trait Instance {
+    /// The instance name of the struct, i.e. its `instance_name` field.
+    fn instance_name(&self) -> &str;
+}
+
+impl<T> Ref<T> {
+    /// The instance name of the struct block pointed at.
+    pub fn name(&self) -> &str {
+        &self.name.value
+    }
+
+    /// The location of the reference.
+    pub fn span(&self) -> &Span {
+        &self.name.span
+    }
+
+    /// Looks up the struct pointed at among `targets`.
+    pub fn resolve<'t, I>(&self, targets: I) -> Option<&'t T>
+    where
+        I: IntoIterator<Item = &'t T>,
+        T: Instance + 't,
+    {
+        targets
+            .into_iter()
+            .find(|target| target.instance_name() == self.name.value)
+    }
+}
+
+impl<T> fmt::Debug for Ref<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Ref").field(&self.name.value).finish()
+    }
+}
+
+impl<T> Clone for Ref<T> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            target: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for Ref<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.name.value == other.name.value
+    }
+}
+
+impl<T> Eq for Ref<T> {}
+
+impl<'de, T: de::Deserialize<'de>> de::Deserialize<'de> for Ref<T> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let type_name = match T::deserialize(TypeNameProbe) {
+            Err(TypeName(Some(type_name))) => type_name,
+            _ => return Err(de::Error::custom("references must point at structs")),
+        };
+        let name = deserializer.deserialize_struct(
+            type_name,
+            REF_FIELDS,
+            SpannedVisitor::<String>(PhantomData),
+        )?;
+        Ok(Ref {
+            name,
+            target: PhantomData,
+        })
+    }
+}
+
+/// The struct type name a type asks for when it is deserialized, if it is a struct.
+#[derive(Debug)]
+struct TypeName(Option<&'static str>);
+
+impl fmt::Display for TypeName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl std::error::Error for TypeName {}
+
+impl de::Error for TypeName {
+    fn custom<M: fmt::Display>(_: M) -> Self {
+        TypeName(None)
+    }
+}
+
+/// Finds out the [`TypeName`] of a type by having it deserialize itself.
+///
+/// [`TypeName`]: struct.TypeName.html
+struct TypeNameProbe;
+
+impl<'de> de::Deserializer<'de> for TypeNameProbe {
+    type Error = TypeName;
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, TypeName> {
+        Err(TypeName(None))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, TypeName> {
+        Err(TypeName(Some(name)))
+    }
+}
+
+/// Keeps track of the struct blocks and references that were deserialized, so that the
+/// references can be checked.
+#[derive(Debug, Default)]
+pub(crate) struct References {
+    /// The instance names of the struct blocks of every type.
+    instances: RefCell<HashMap<&'static str, Vec<String>>>,
+    /// The type name of the target, the instance name and the location of every reference.
+    references: RefCell<Vec<(&'static str, String, Span)>>,
+}
+
+impl References {
+    pub(crate) fn add_instance(&self, type_name: &'static str, instance_name: &str) {
+        self.instances
+            .borrow_mut()
+            .entry(type_name)
+            .or_default()
+            .push(instance_name.to_string());
+    }
+
+    pub(crate) fn add_reference(&self, type_name: &'static str, name: &str, span: &Span) {
+        let reference = (type_name, name.to_string(), span.clone());
+        self.references.borrow_mut().push(reference);
+    }
+
+    /// The errors for all the references to struct blocks that don't exist, in the order the
+    /// references were deserialized.
+    pub(crate) fn dangling(&self) -> Vec<Error> {
+        let instances = self.instances.borrow();
+        let references = self.references.borrow();
+        let mut errors = Vec::new();
+        for (type_name, name, span) in references.iter() {
+            let names = instances
+                .get(type_name)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            if !names.contains(name) {
+                errors.push(Error::DanglingReference {
+                    name: name.clone(),
+                    type_name: type_name.to_string(),
+                    did_you_mean: suggestions::closest(name, names.iter().map(String::as_str)),
+                    range: span.clone(),
+                });
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast;
+    use crate::de::{Deserializer, Error};
+    use crate::fixtures::{ItemType, Monster};
+    use serde::Deserialize;
+    use serde_derive::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename = "world")]
+    struct World {
+        #[allow(dead_code)]
+        instance_name: String,
+        item_type: Vec<ItemType>,
+        monster: Vec<Monster>,
+    }
+
+    #[test]
+    fn references() {
+        let source = r#"world {
+  item_type "Gold" { cost = 1 }
+  item_type "Chest" { cost = 5 drops = ["Gold", "Potion"] }
+  monster "Orc" { hp = 10 drops = ["Gold", "Chset", "Orc"] }
+}"#;
+        let check = |de: &mut Deserializer| {
+            let world = World::deserialize(&mut *de).unwrap();
+            let gold = world.monster[0].drops[0].resolve(&world.item_type);
+            assert_eq!(gold.unwrap().instance_name, "Gold");
+            assert_eq!(world.item_type[1].drops[1].name(), "Potion");
+
+            let errors = de.dangling_references();
+            let names = errors.iter().map(|error| match error {
+                Error::DanglingReference {
+                    name, did_you_mean, ..
+                } => (name.as_str(), did_you_mean.clone()),
+                error => panic!("unexpected error: {:?}", error),
+            });
+            assert_eq!(
+                names.collect::<Vec<_>>(),
+                [
+                    ("Potion", vec![]),
+                    ("Chset", vec!["Chest".to_string()]),
+                    ("Orc", vec![])
+                ]
+            );
+            assert_eq!(errors[0].range(), Some(88..96));
+            assert!(de.check_references().is_err());
+        };
+
+        check(&mut Deserializer::new(source));
+        check(&mut Deserializer::from_file(ast::parse(source).unwrap()));
+    }
+}
@@ -0,0 +1,187 @@
+use crate::de::{Error, Result, SPANNED};
+use crate::lexer::{Position, Span};
+use serde::de::{self, IntoDeserializer, Visitor};
+use serde_derive::Deserialize;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+const FIELDS: &[&str] = &[
+    "start",
+    "end",
+    "start_line",
+    "start_column",
+    "end_line",
+    "end_column",
+    "value",
+];
+
+/// A value along with its location in the config file.
+///
+/// This works for property values (including lists), flags and whole struct blocks, and is useful
+/// for pointing back at the config file when a value that was deserialized successfully later
+/// turns out to be invalid.
+///
+/// ```
+/// use serde_derive::Deserialize;
+/// use serde_tcod_config_parser::de::{Deserializer, Spanned};
+///
+/// #[derive(Deserialize)]
+/// #[serde(rename = "item_type")]
+/// struct ItemType {
+///     instance_name: String,
+///     sprite: Spanned<u32>,
+/// }
+///
+/// let sword: ItemType = Deserializer::from_str("item_type \"Sword\" {\n  sprite = 900\n}").unwrap();
+/// assert_eq!(sword.sprite.span, 31..34);
+/// assert_eq!((sword.sprite.start.line, sword.sprite.start.column), (2, 12));
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone)]
+pub struct Spanned<T> {
+    /// The value itself.
+    pub value: T,
+    /// The location of the value, as a range of byte offsets.
+    pub span: Span,
+    /// The line and column the value starts at.
+    pub start: Position,
+    /// The line and column the value ends at, i.e. just past its last character.
+    pub end: Position,
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Spanned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "snake_case")]
+enum Field {
+    Start,
+    End,
+    StartLine,
+    StartColumn,
+    EndLine,
+    EndColumn,
+    Value,
+}
+
+/// Reads a [`Spanned`] from the map handed to it by a [`SpannedAccess`].
+///
+/// [`Spanned`]: struct.Spanned.html
+/// [`SpannedAccess`]: struct.SpannedAccess.html
+pub(crate) struct SpannedVisitor<T>(pub(crate) PhantomData<T>);
+
+impl<'de, T: de::Deserialize<'de>> Visitor<'de> for SpannedVisitor<T> {
+    type Value = Spanned<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a spanned value")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> std::result::Result<Self::Value, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        let mut span = 0..0;
+        let mut start = Position::default();
+        let mut end = Position::default();
+        let mut value = None;
+        while let Some(field) = map.next_key()? {
+            match field {
+                Field::Start => span.start = map.next_value()?,
+                Field::End => span.end = map.next_value()?,
+                Field::StartLine => start.line = map.next_value()?,
+                Field::StartColumn => start.column = map.next_value()?,
+                Field::EndLine => end.line = map.next_value()?,
+                Field::EndColumn => end.column = map.next_value()?,
+                Field::Value => value = Some(map.next_value()?),
+            }
+        }
+
+        Ok(Spanned {
+            value: value.ok_or_else(|| de::Error::missing_field("value"))?,
+            span,
+            start,
+            end,
+        })
+    }
+}
+
+impl<'de, T: de::Deserialize<'de>> de::Deserialize<'de> for Spanned<T> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_struct(SPANNED, FIELDS, SpannedVisitor(PhantomData))
+    }
+}
+
+/// Hands a [`Spanned`] its location, followed by its value, which is deserialized by `de`.
+///
+/// [`Spanned`]: struct.Spanned.html
+pub(crate) struct SpannedAccess<D> {
+    numbers: [usize; 6],
+    index: usize,
+    de: Option<D>,
+}
+
+impl<D> SpannedAccess<D> {
+    pub(crate) fn new(source: &str, span: Span, de: D) -> Self {
+        let start = Position::of(source, span.start);
+        let end = Position::of(source, span.end);
+        Self {
+            numbers: [
+                span.start,
+                span.end,
+                start.line,
+                start.column,
+                end.line,
+                end.column,
+            ],
+            index: 0,
+            de: Some(de),
+        }
+    }
+}
+
+impl<'de, D> de::MapAccess<'de> for SpannedAccess<D>
+where
+    D: de::Deserializer<'de, Error = Error>,
+{
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match FIELDS.get(self.index) {
+            Some(field) => seed.deserialize(field.into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let index = self.index;
+        self.index += 1;
+        match self.numbers.get(index) {
+            Some(&number) => seed.deserialize(number.into_deserializer()),
+            None => match self.de.take() {
+                Some(de) => seed.deserialize(de),
+                None => Err(de::Error::custom("value requested twice")),
+            },
+        }
+    }
+}
@@ -1,22 +1,78 @@
-use crate::de::{Deserializer, Error};
+use crate::de::{at_value_span, Deserializer, Error};
 use crate::lexer::Token;
 use logos::Lexer;
 use serde::de::{self, IntoDeserializer};
+use std::collections::HashMap;
+use std::ops::Range;
 
 pub struct StructInternalAccess<'a, 'de> {
     de: &'a mut Deserializer<'de>,
     instance_name: Option<&'de str>,
-    lexer: Option<Lexer<Token, &'de str>>,
+    fields: &'static [&'static str],
+    lexer: Option<(Lexer<Token, &'de str>, usize)>,
+    seen: HashMap<&'de str, Range<usize>>,
+    last_occurrences: HashMap<&'de str, usize>,
 }
 
 impl<'a, 'de> StructInternalAccess<'a, 'de> {
-    pub fn new(de: &'a mut Deserializer<'de>, instance_name: &'de str) -> Self {
+    pub fn new(
+        de: &'a mut Deserializer<'de>,
+        instance_name: &'de str,
+        fields: &'static [&'static str],
+    ) -> Self {
+        let last_occurrences = if de.options.last_property_wins {
+            last_occurrences(de.lexer.clone())
+        } else {
+            HashMap::new()
+        };
+
         Self {
             de,
             instance_name: Some(instance_name),
+            fields,
             lexer: None,
+            seen: HashMap::new(),
+            last_occurrences,
+        }
+    }
+}
+
+/// Finds the position of the last occurrence of every property and flag in the struct block
+/// starting at `lexer`, so that earlier occurrences can be skipped.
+fn last_occurrences(mut lexer: Lexer<Token, &str>) -> HashMap<&str, usize> {
+    let mut result = HashMap::new();
+    let mut depth = 0;
+    let mut key = None;
+    loop {
+        match lexer.token {
+            Token::EndOfProgram => break,
+            Token::BraceOpen | Token::BracketOpen => depth += 1,
+            Token::BraceClose | Token::BracketClose if depth == 0 => break,
+            Token::BraceClose | Token::BracketClose => depth -= 1,
+            _ => {}
+        }
+
+        if let Some((name, start)) = key.take() {
+            match lexer.token {
+                Token::Assign | Token::Identifier | Token::BraceClose => {
+                    result.insert(name, start);
+                }
+                _ => {}
+            }
+        }
+
+        if depth == 0 && lexer.token == Token::Identifier {
+            key = Some((lexer.slice(), lexer.range().start));
         }
+
+        lexer.advance();
+    }
+
+    if let Some((name, start)) = key {
+        result.insert(name, start);
     }
+
+    result
 }
 
 impl<'de: 'a, 'a> de::MapAccess<'de> for StructInternalAccess<'a, 'de> {
@@ -35,29 +91,65 @@ impl<'de: 'a, 'a> de::MapAccess<'de> for StructInternalAccess<'a, 'de> {
                 .map(Some);
         }
 
-        if self.de.lexer.token == Token::BraceClose {
-            self.de.lexer.advance();
-            return Ok(None);
-        }
+        loop {
+            if self.de.lexer.token == Token::BraceClose {
+                self.de.advance();
+                return Ok(None);
+            }
 
-        if self.de.lexer.token != Token::Identifier {
-            return unexpected_token!(self.de.lexer, "<field>");
-        }
-        let field = self.de.lexer.slice();
-
-        self.lexer = Some(self.de.lexer.clone());
-        self.de.lexer.advance();
-
-        if self.de.lexer.token == Token::Assign
-            || self.de.lexer.token == Token::Text
-            || self.de.lexer.token == Token::BraceOpen
-            || self.de.lexer.token == Token::Identifier
-            || self.de.lexer.token == Token::BraceClose
-        {
-            seed.deserialize(field.into_deserializer()).map(Some)
-        } else {
-            self.lexer = None;
-            Ok(None)
+            if self.de.lexer.token != Token::Identifier {
+                return unexpected_token!(self.de.lexer, "<field>");
+            }
+            let field = self.de.lexer.slice();
+            let range = self.de.lexer.range();
+            self.de.take_comments();
+
+            self.lexer = Some((self.de.lexer.clone(), self.de.previous_end));
+            self.de.advance();
+
+            match self.de.lexer.token {
+                Token::Assign | Token::Identifier | Token::BraceClose => {
+                    if let Some(&last) = self.last_occurrences.get(field) {
+                        if last != range.start {
+                            self.lexer = None;
+                            if self.de.lexer.token == Token::Assign {
+                                self.de.advance();
+                                self.de.skip_value()?;
+                            }
+                            continue;
+                        }
+                    }
+
+                    if let Some(first) = self.seen.insert(field, range.clone()) {
+                        return Err(Error::DuplicateProperty {
+                            name: field.to_string(),
+                            first,
+                            second: range,
+                        });
+                    }
+
+                    return seed
+                        .deserialize(field.into_deserializer())
+                        .map(Some)
+                        .map_err(|error| Error::at(error, &range));
+                }
+                Token::Text | Token::BraceOpen => {
+                    let field = self
+                        .de
+                        .options
+                        .type_names
+                        .find_field(field, self.fields)
+                        .unwrap_or(field);
+                    return seed
+                        .deserialize(field.into_deserializer())
+                        .map(Some)
+                        .map_err(|error| Error::at(error, &range));
+                }
+                _ => {
+                    self.lexer = None;
+                    return Ok(None);
+                }
+            }
         }
     }
 
@@ -75,21 +167,24 @@ impl<'de: 'a, 'a> de::MapAccess<'de> for StructInternalAccess<'a, 'de> {
         match self.de.lexer.token {
             Token::Assign => {
                 self.lexer = None;
-                self.de.lexer.advance();
-
-                match self.de.lexer.token {
-                    Token::Text
-                    | Token::Char
-                    | Token::Integer
-                    | Token::Hex
-                    | Token::Float
-                    | Token::BracketOpen => seed.deserialize(&mut *self.de),
-                    _ => unexpected_token!(self.de.lexer, "<value>"),
+                self.de.advance();
+
+                if !self.de.at_value() {
+                    return unexpected_token!(self.de.lexer, "<value>");
                 }
+                let value = self.de.lexer.clone();
+                seed.deserialize(&mut *self.de)
+                    .map_err(|error| at_value_span(error, value))
             }
             Token::Text | Token::BraceOpen | Token::Identifier | Token::BraceClose => {
-                self.de.lexer = self.lexer.take().unwrap();
+                let (lexer, previous_end) = match self.lexer.take() {
+                    Some(lexer) => lexer,
+                    None => return unexpected_token!(self.de.lexer, "="),
+                };
+                self.de.lexer = lexer.clone();
+                self.de.previous_end = previous_end;
                 seed.deserialize(&mut *self.de)
+                    .map_err(|error| at_value_span(error, lexer))
             }
             _ => unexpected_token!(self.de.lexer, "= or \"<name>\""),
         }
@@ -28,12 +28,18 @@ impl<'de: 'a, 'a> de::SeqAccess<'de> for StructSeqAccess<'a, 'de> {
     {
         if self.de.lexer.token == Token::Identifier {
             if let Some(type_name) = self.type_name {
-                if type_name != self.de.lexer.slice() {
+                if !self
+                    .de
+                    .options
+                    .type_names
+                    .same_type(type_name, self.de.lexer.slice())
+                {
                     return Ok(None);
                 }
             } else {
                 self.type_name = Some(self.de.lexer.slice());
             }
+            self.de.take_comments();
             seed.deserialize(&mut *self.de).map(Some)
         } else if self.de.lexer.token == Token::BraceClose {
             Ok(None)
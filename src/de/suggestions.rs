@@ -0,0 +1,85 @@
+/// Finds the candidates that are closest to `name` by edit distance, as long as they are close
+/// enough to plausibly be what was meant.
+pub(crate) fn closest<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+    let max_distance = std::cmp::max(1, name.chars().count() / 3);
+
+    let name = name.to_lowercase();
+    let mut best = max_distance + 1;
+    let mut result: Vec<String> = Vec::new();
+    for candidate in candidates {
+        let distance = edit_distance(&name, &candidate.to_lowercase());
+        if distance < best {
+            best = distance;
+            result.clear();
+        }
+        if distance == best && !result.iter().any(|r| r == candidate) {
+            result.push(candidate.to_string());
+        }
+    }
+
+    result
+}
+
+/// Formats a list of suggestions as a "did you mean" hint, or nothing if there are none.
+pub(crate) fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions.split_last() {
+        None => String::new(),
+        Some((last, [])) => format!(", did you mean `{}`?", last),
+        Some((last, rest)) => {
+            let rest = rest
+                .iter()
+                .map(|s| format!("`{}`", s))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(", did you mean {} or `{}`?", rest, last)
+        }
+    }
+}
+
+/// The Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == cb { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{closest, did_you_mean};
+
+    #[test]
+    fn closest_candidates() {
+        let fields = ["instance_name", "health", "damage", "defense"];
+
+        assert_eq!(closest("helth", fields.iter().copied()), vec!["health"]);
+        assert_eq!(closest("Damage", fields.iter().copied()), vec!["damage"]);
+        assert!(closest("speed", fields.iter().copied()).is_empty());
+    }
+
+    #[test]
+    fn format_suggestions() {
+        assert_eq!(did_you_mean(&[]), "");
+        assert_eq!(
+            did_you_mean(&["health".to_string()]),
+            ", did you mean `health`?"
+        );
+        assert_eq!(
+            did_you_mean(&["cat".to_string(), "car".to_string(), "cap".to_string()]),
+            ", did you mean `cat`, `car` or `cap`?"
+        );
+    }
+}
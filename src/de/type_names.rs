@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+/// Determines how the struct type names found in a config file are compared against the names
+/// of the types being deserialized.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum TypeNameMatching {
+    /// Type names must match exactly. This is the default.
+    #[default]
+    Exact,
+    /// Type names are compared without regard to ASCII case, so `ITEM_TYPE` matches `item_type`.
+    IgnoreCase,
+    /// Type names are compared without regard to ASCII case and underscores, so `ItemType`
+    /// matches `item_type`.
+    IgnoreCaseAndUnderscores,
+}
+
+impl TypeNameMatching {
+    fn matches(self, a: &str, b: &str) -> bool {
+        match self {
+            TypeNameMatching::Exact => a == b,
+            TypeNameMatching::IgnoreCase => a.eq_ignore_ascii_case(b),
+            TypeNameMatching::IgnoreCaseAndUnderscores => {
+                let a = a.bytes().filter(|&c| c != b'_');
+                let b = b.bytes().filter(|&c| c != b'_');
+                a.map(|c| c.to_ascii_lowercase())
+                    .eq(b.map(|c| c.to_ascii_lowercase()))
+            }
+        }
+    }
+}
+
+/// Keeps track of which struct type names in a config file are accepted for a given type.
+#[derive(Debug, Default)]
+pub(crate) struct TypeNames {
+    pub(crate) matching: TypeNameMatching,
+    aliases: HashMap<&'static str, Vec<String>>,
+}
+
+impl TypeNames {
+    pub(crate) fn add_alias(&mut self, type_name: &'static str, alias: String) {
+        self.aliases.entry(type_name).or_default().push(alias);
+    }
+
+    /// Whether `name`, as found in a config file, is accepted for the type named `type_name`.
+    pub(crate) fn matches(&self, name: &str, type_name: &str) -> bool {
+        if self.matching.matches(name, type_name) {
+            return true;
+        }
+
+        self.aliases
+            .get(type_name)
+            .into_iter()
+            .flatten()
+            .any(|alias| self.matching.matches(name, alias))
+    }
+
+    /// All names accepted for the type named `type_name`.
+    pub(crate) fn names_for<'a>(&'a self, type_name: &'a str) -> impl Iterator<Item = &'a str> {
+        let aliases = self.aliases.get(type_name).into_iter().flatten();
+        std::iter::once(type_name).chain(aliases.map(String::as_str))
+    }
+
+    /// Whether the two names, as found in a config file, refer to the same type.
+    pub(crate) fn same_type(&self, a: &str, b: &str) -> bool {
+        self.matching.matches(a, b)
+            || self
+                .aliases
+                .keys()
+                .any(|type_name| self.matches(a, type_name) && self.matches(b, type_name))
+    }
+
+    /// Finds the field among `fields` whose name is accepted for the struct type `name`.
+    pub(crate) fn find_field(
+        &self,
+        name: &str,
+        fields: &'static [&'static str],
+    ) -> Option<&'static str> {
+        fields
+            .iter()
+            .find(|&&field| field == name)
+            .or_else(|| fields.iter().find(|&&field| self.matches(name, field)))
+            .copied()
+    }
+}
@@ -0,0 +1,650 @@
+//! Format-preserving editing of libtcod config files.
+//!
+//! A [`Document`] keeps every comment, blank line and bit of alignment of the file it was parsed
+//! from, so it can be used to change a few values in a hand-written file without disturbing the
+//! rest of it. An untouched document is written back exactly as it was read.
+//!
+//! Properties and struct blocks are addressed by paths like `item_type#Sword.cost`, where each
+//! segment but the last names a struct block by its type name, optionally followed by `#` and an
+//! instance name. Without an instance name, the first struct block of that type is used.
+//!
+//! ```
+//! use serde_tcod_config_parser::document::Document;
+//!
+//! let mut document: Document = "item_type \"Sword\" {\n    cost = 10 // in gold\n}\n"
+//!     .parse()
+//!     .unwrap();
+//!
+//! document.set("item_type#Sword.cost", 12).unwrap();
+//! document.set("item_type#Sword.weight", 3.5).unwrap();
+//!
+//! assert_eq!(
+//!     document.to_string(),
+//!     "item_type \"Sword\" {\n    cost = 12 // in gold\n    weight = 3.5\n}\n"
+//! );
+//! ```
+//!
+//! [`Document`]: struct.Document.html
+use crate::ast::{self, Color, ValueKind};
+use crate::de::{Error, Result};
+use crate::path::{self, Segment};
+use std::fmt::{self, Display, Write};
+use std::str::FromStr;
+
+mod parser;
+
+/// A libtcod config file that can be edited without losing its formatting.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Document {
+    structs: Vec<Block>,
+    trailing: String,
+}
+
+/// A struct block within a [`Document`].
+///
+/// [`Document`]: struct.Document.html
+#[derive(Debug, PartialEq, Clone)]
+pub struct Block {
+    leading: String,
+    type_name: String,
+    instance_name: Option<(String, String)>,
+    before_brace: String,
+    items: Vec<Node>,
+    before_close: String,
+    trailing: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum Node {
+    Property(PropertyNode),
+    Flag(FlagNode),
+    Struct(Block),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct PropertyNode {
+    leading: String,
+    name: String,
+    before_assign: String,
+    after_assign: String,
+    value: String,
+    trailing: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct FlagNode {
+    leading: String,
+    name: String,
+    trailing: String,
+}
+
+/// A value to be written into a [`Document`].
+///
+/// [`Document`]: struct.Document.html
+#[derive(Debug, PartialEq, Clone)]
+pub enum Literal {
+    /// A string. Strings cannot contain `"`.
+    String(String),
+    /// A char.
+    Char(char),
+    /// An integer.
+    Integer(i64),
+    /// A floating point number. Infinity and NaN cannot be represented.
+    Float(f64),
+    /// A color.
+    Color(Color),
+    /// A list of values.
+    List(Vec<Literal>),
+}
+
+impl Document {
+    /// Parses a libtcod config file into a document.
+    pub fn parse(source: &str) -> Result<Self> {
+        ast::parse(source)?;
+        Ok(parser::Parser::new(source).parse_document())
+    }
+
+    /// The struct blocks at the top level of the document.
+    pub fn structs(&self) -> &[Block] {
+        &self.structs
+    }
+
+    /// The value of the property at `path`, if there is one.
+    pub fn get(&self, path: &str) -> Option<ValueKind<'_>> {
+        let (parent, last) = split(path)?;
+        let block = self.block(&parent)?;
+        match &block.items[block.property(last)?] {
+            Node::Property(property) => ast::parser::Parser::new(&property.value)
+                .parse_value()
+                .ok()
+                .map(|value| value.kind),
+            _ => None,
+        }
+    }
+
+    /// The struct block at `path`, if there is one.
+    pub fn get_struct(&self, path: &str) -> Option<&Block> {
+        self.block(&path::segments(path))
+    }
+
+    /// Whether there is a property, flag or struct block at `path`.
+    pub fn contains(&self, path: &str) -> bool {
+        let (parent, last) = match split(path) {
+            Some(split) => split,
+            None => return false,
+        };
+        match self.block(&parent) {
+            Some(block) => block
+                .flag(last)
+                .or_else(|| block.property(last))
+                .or_else(|| block.child(&last))
+                .is_some(),
+            None => self.get_struct(path).is_some(),
+        }
+    }
+
+    /// Sets the value of the property at `path`, adding the property to the end of its struct
+    /// block if it isn't already there. A flag of the same name is turned into the property.
+    pub fn set(&mut self, path: &str, value: impl Into<Literal>) -> Result {
+        let value = value.into().to_source()?;
+        let (parent, last) = split(path).ok_or_else(|| not_found(path))?;
+        let block = self.block_mut(&parent).ok_or_else(|| not_found(path))?;
+
+        if let Some(index) = block.property(last) {
+            if let Node::Property(property) = &mut block.items[index] {
+                property.value = value;
+            }
+        } else if let Some(index) = block.flag(last) {
+            if let Node::Flag(flag) = &mut block.items[index] {
+                let property = PropertyNode {
+                    leading: std::mem::take(&mut flag.leading),
+                    name: std::mem::take(&mut flag.name),
+                    before_assign: " ".to_string(),
+                    after_assign: " ".to_string(),
+                    value,
+                    trailing: std::mem::take(&mut flag.trailing),
+                };
+                block.items[index] = Node::Property(property);
+            }
+        } else {
+            let leading = block.child_leading();
+            block.push(Node::Property(PropertyNode {
+                leading,
+                name: last.name.to_string(),
+                before_assign: " ".to_string(),
+                after_assign: " ".to_string(),
+                value,
+                trailing: String::new(),
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// Adds the flag at `path` if `set` is `true`, and removes it otherwise.
+    pub fn set_flag(&mut self, path: &str, set: bool) -> Result {
+        let (parent, last) = split(path).ok_or_else(|| not_found(path))?;
+        let block = self.block_mut(&parent).ok_or_else(|| not_found(path))?;
+
+        match (block.flag(last), set) {
+            (Some(index), false) => {
+                block.items.remove(index);
+            }
+            (None, true) => {
+                let leading = block.child_leading();
+                block.push(Node::Flag(FlagNode {
+                    leading,
+                    name: last.name.to_string(),
+                    trailing: String::new(),
+                }));
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Adds a new, empty struct block to the end of the struct block at `path`, or to the end of
+    /// the document if `path` is empty.
+    pub fn insert_struct(
+        &mut self,
+        path: &str,
+        type_name: &str,
+        instance_name: Option<&str>,
+    ) -> Result {
+        let instance_name = match instance_name {
+            Some(name) => Some((" ".to_string(), Literal::String(name.into()).to_source()?)),
+            None => None,
+        };
+
+        let segments = path::segments(path);
+        let leading = if segments.is_empty() {
+            if self.structs.is_empty() { "" } else { "\n\n" }.to_string()
+        } else {
+            self.block(&segments)
+                .ok_or_else(|| not_found(path))?
+                .child_leading()
+        };
+
+        let block = Block {
+            before_close: match leading.rfind('\n') {
+                Some(index) => leading[index..].to_string(),
+                None => " ".to_string(),
+            },
+            leading,
+            type_name: type_name.to_string(),
+            instance_name,
+            before_brace: " ".to_string(),
+            items: Vec::new(),
+            trailing: String::new(),
+        };
+
+        match self.block_mut(&segments) {
+            Some(parent) => parent.push(Node::Struct(block)),
+            None => self.structs.push(block),
+        }
+
+        Ok(())
+    }
+
+    /// Removes the property, flag or struct block at `path`, returning whether there was one.
+    pub fn remove(&mut self, path: &str) -> bool {
+        let (parent, last) = match split(path) {
+            Some(split) => split,
+            None => return false,
+        };
+
+        if parent.is_empty() {
+            return match self.structs.iter().position(|b| b.is(&last)) {
+                Some(index) => {
+                    self.structs.remove(index);
+                    true
+                }
+                None => false,
+            };
+        }
+
+        let block = match self.block_mut(&parent) {
+            Some(block) => block,
+            None => return false,
+        };
+        let index = block
+            .property(last)
+            .or_else(|| block.flag(last))
+            .or_else(|| block.child(&last));
+        match index {
+            Some(index) => {
+                block.items.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn block(&self, segments: &[Segment]) -> Option<&Block> {
+        let (first, rest) = segments.split_first()?;
+        let mut block = self.structs.iter().find(|block| block.is(first))?;
+        for segment in rest {
+            block = match &block.items[block.child(segment)?] {
+                Node::Struct(child) => child,
+                _ => return None,
+            };
+        }
+
+        Some(block)
+    }
+
+    fn block_mut(&mut self, segments: &[Segment]) -> Option<&mut Block> {
+        let (first, rest) = segments.split_first()?;
+        let mut block = self.structs.iter_mut().find(|block| block.is(first))?;
+        for segment in rest {
+            let index = block.child(segment)?;
+            block = match &mut block.items[index] {
+                Node::Struct(child) => child,
+                _ => return None,
+            };
+        }
+
+        Some(block)
+    }
+}
+
+impl Node {
+    pub(crate) fn trailing_mut(&mut self) -> &mut String {
+        match self {
+            Node::Property(property) => &mut property.trailing,
+            Node::Flag(flag) => &mut flag.trailing,
+            Node::Struct(block) => &mut block.trailing,
+        }
+    }
+}
+
+impl Block {
+    /// The type name of the struct block.
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    /// The instance name of the struct block, if it has one.
+    pub fn instance_name(&self) -> Option<&str> {
+        self.instance_name
+            .as_ref()
+            .map(|(_, name)| &name[1..name.len() - 1])
+    }
+
+    fn is(&self, segment: &Segment) -> bool {
+        segment.matches(&self.type_name, self.instance_name())
+    }
+
+    fn property(&self, segment: Segment) -> Option<usize> {
+        self.items.iter().position(|item| match item {
+            Node::Property(property) => {
+                segment.instance_name.is_none() && property.name == segment.name
+            }
+            _ => false,
+        })
+    }
+
+    fn flag(&self, segment: Segment) -> Option<usize> {
+        self.items.iter().position(|item| match item {
+            Node::Flag(flag) => segment.instance_name.is_none() && flag.name == segment.name,
+            _ => false,
+        })
+    }
+
+    fn child(&self, segment: &Segment) -> Option<usize> {
+        self.items.iter().position(|item| match item {
+            Node::Struct(block) => block.is(segment),
+            _ => false,
+        })
+    }
+
+    fn push(&mut self, node: Node) {
+        if self.before_close.is_empty() {
+            self.before_close = " ".to_string();
+        }
+        self.items.push(node);
+    }
+
+    /// The whitespace to put in front of a new item at the end of this block, following the
+    /// indentation of the existing items.
+    fn child_leading(&self) -> String {
+        let (reference, extra) = match self.items.last() {
+            Some(Node::Property(property)) => (&property.leading, ""),
+            Some(Node::Flag(flag)) => (&flag.leading, ""),
+            Some(Node::Struct(block)) => (&block.leading, ""),
+            None => (&self.before_close, "    "),
+        };
+
+        match reference.rfind('\n') {
+            Some(index) => {
+                let line = &reference[index + 1..];
+                let indentation = &line[..line.len() - line.trim_start().len()];
+                format!("\n{}{}", indentation, extra)
+            }
+            None => " ".to_string(),
+        }
+    }
+}
+
+impl Literal {
+    /// How the value is written in a config file.
+    fn to_source(&self) -> Result<String> {
+        let unrepresentable = || Error::UnrepresentableValue {
+            value: format!("{:?}", self),
+        };
+
+        Ok(match self {
+            Literal::String(value) if value.contains('"') => return Err(unrepresentable()),
+            Literal::String(value) => format!("\"{}\"", value),
+            Literal::Char(value) => match value {
+                '\n' => "'\\n'".to_string(),
+                '\t' => "'\\t'".to_string(),
+                '\r' => "'\\r'".to_string(),
+                '\\' => "'\\\\'".to_string(),
+                '\'' => "'\\''".to_string(),
+                c if (c.is_control() || !c.is_ascii()) && (*c as u32) <= 0xFF => {
+                    format!("'\\x{:02X}'", *c as u32)
+                }
+                c if c.is_control() || !c.is_ascii() => return Err(unrepresentable()),
+                c => format!("'{}'", c),
+            },
+            Literal::Integer(value) => value.to_string(),
+            Literal::Float(value) if !value.is_finite() => return Err(unrepresentable()),
+            Literal::Float(value) => {
+                let mut result = value.to_string();
+                if !result.contains('.') {
+                    result.push_str(".0");
+                }
+                result
+            }
+            Literal::Color(color) => format!("#{:02X}{:02X}{:02X}", color.r, color.g, color.b),
+            Literal::List(values) => {
+                let values = values
+                    .iter()
+                    .map(Literal::to_source)
+                    .collect::<Result<Vec<_>>>()?;
+                format!("[{}]", values.join(", "))
+            }
+        })
+    }
+}
+
+macro_rules! literal_from {
+    ($($ty: ty => $variant: ident),*) => {
+        $(
+            impl From<$ty> for Literal {
+                fn from(value: $ty) -> Self {
+                    Literal::$variant(value.into())
+                }
+            }
+        )*
+    };
+}
+
+literal_from! {
+    &str => String,
+    String => String,
+    char => Char,
+    i8 => Integer,
+    i16 => Integer,
+    i32 => Integer,
+    i64 => Integer,
+    u8 => Integer,
+    u16 => Integer,
+    u32 => Integer,
+    f32 => Float,
+    f64 => Float,
+    Color => Color
+}
+
+impl<T: Into<Literal>> From<Vec<T>> for Literal {
+    fn from(values: Vec<T>) -> Self {
+        Literal::List(values.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Splits a path into the segments of the parent struct block and the last segment.
+fn split(path: &str) -> Option<(Vec<Segment<'_>>, Segment<'_>)> {
+    let mut segments = path::segments(path);
+    let last = segments.pop()?;
+    Some((segments, last))
+}
+
+fn not_found(path: &str) -> Error {
+    Error::PathNotFound {
+        path: path.to_string(),
+    }
+}
+
+impl FromStr for Document {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        Document::parse(source)
+    }
+}
+
+impl Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for block in &self.structs {
+            block.fmt(f)?;
+        }
+        f.write_str(&self.trailing)
+    }
+}
+
+impl Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.leading)?;
+        f.write_str(&self.type_name)?;
+        if let Some((leading, name)) = &self.instance_name {
+            f.write_str(leading)?;
+            f.write_str(name)?;
+        }
+        f.write_str(&self.before_brace)?;
+        f.write_char('{')?;
+        for item in &self.items {
+            match item {
+                Node::Property(property) => {
+                    f.write_str(&property.leading)?;
+                    f.write_str(&property.name)?;
+                    f.write_str(&property.before_assign)?;
+                    f.write_char('=')?;
+                    f.write_str(&property.after_assign)?;
+                    f.write_str(&property.value)?;
+                    f.write_str(&property.trailing)?;
+                }
+                Node::Flag(flag) => {
+                    f.write_str(&flag.leading)?;
+                    f.write_str(&flag.name)?;
+                    f.write_str(&flag.trailing)?;
+                }
+                Node::Struct(block) => block.fmt(f)?,
+            }
+        }
+        f.write_str(&self.before_close)?;
+        f.write_char('}')?;
+        f.write_str(&self.trailing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Document;
+    use crate::ast::{self, ValueKind};
+    use crate::de::Error;
+
+    const SOURCE: &str = "// Weapons
+item_type \"Sword\" {
+    cost   = 10  // in gold
+    damage = [1, /* min */ 6]
+    /* nested /* comment */ */
+    stackable
+    effect {
+        name = \"slash\"
+               \"ing\"
+    }
+}
+
+item_type \"Bow\" {}
+";
+
+    #[test]
+    fn round_trip() {
+        let document: Document = SOURCE.parse().unwrap();
+
+        assert_eq!(document.to_string(), SOURCE);
+    }
+
+    #[test]
+    fn get() {
+        let document: Document = SOURCE.parse().unwrap();
+
+        assert_eq!(
+            document.get("item_type#Sword.cost"),
+            Some(ValueKind::Integer(10))
+        );
+        assert_eq!(
+            document.get("item_type.effect.name"),
+            Some(ValueKind::String("slashing".into()))
+        );
+        assert_eq!(document.get("item_type#Bow.cost"), None);
+        assert!(document.contains("item_type#Sword.stackable"));
+        assert!(document.contains("item_type#Sword.effect"));
+        assert!(!document.contains("item_type#Bow.stackable"));
+        assert_eq!(
+            document
+                .get_struct("item_type#Bow")
+                .unwrap()
+                .instance_name(),
+            Some("Bow")
+        );
+    }
+
+    #[test]
+    fn edit() {
+        let mut document: Document = SOURCE.parse().unwrap();
+
+        document.set("item_type#Sword.cost", 12).unwrap();
+        document.set("item_type#Sword.effect.power", 1.0).unwrap();
+        document
+            .set_flag("item_type#Sword.stackable", false)
+            .unwrap();
+        document.set("item_type#Bow.cost", 7).unwrap();
+        assert!(document.remove("item_type#Sword.damage"));
+        assert!(!document.remove("item_type#Sword.damage"));
+        document
+            .insert_struct("", "item_type", Some("Axe"))
+            .unwrap();
+        document
+            .insert_struct("item_type#Axe", "effect", None)
+            .unwrap();
+        document.set("item_type#Axe.effect.name", "chop").unwrap();
+        assert!(document.set("item_type#Club.cost", 1).is_err());
+
+        assert_eq!(
+            document.to_string(),
+            "// Weapons
+item_type \"Sword\" {
+    cost   = 12  // in gold
+    effect {
+        name = \"slash\"
+               \"ing\"
+        power = 1.0
+    }
+}
+
+item_type \"Bow\" { cost = 7 }
+
+item_type \"Axe\" {
+    effect {
+        name = \"chop\"
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn set_flag_value() {
+        let mut document: Document = "a { x = 1 flag }".parse().unwrap();
+        document.set("a.flag", 2).unwrap();
+        assert_eq!(document.to_string(), "a { x = 1 flag = 2 }");
+        assert!(ast::parse(&document.to_string()).is_ok());
+    }
+
+    #[test]
+    fn set_chars() {
+        let mut document: Document = "a { }".parse().unwrap();
+        for c in ['a', '\n', '\u{7F}', '\u{E9}', '\u{FF}'] {
+            document.set("a.c", c).unwrap();
+            let document: Document = document.to_string().parse().unwrap();
+            assert_eq!(document.get("a.c"), Some(ValueKind::Char(c)));
+        }
+        assert!(matches!(
+            document.set("a.c", '\u{100}'),
+            Err(Error::UnrepresentableValue { .. })
+        ));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::document::{Block, Document, FlagNode, Node, PropertyNode};
+use crate::lexer::{TokenKind, Tokenizer};
+
+/// Builds the concrete syntax tree of a document that is already known to be valid.
+pub(crate) struct Parser<'a> {
+    tokens: Tokenizer<'a>,
+}
+
+impl<'a> Parser<'a> {
+    pub(crate) fn new(source: &'a str) -> Self {
+        Self {
+            tokens: Tokenizer::new(source).with_trivia(true),
+        }
+    }
+
+    pub(crate) fn parse_document(&mut self) -> Document {
+        let mut structs: Vec<Block> = Vec::new();
+        loop {
+            let (mut leading, token) = self.next();
+            if let Some(previous) = structs.last_mut() {
+                previous.trailing = split_trailing(&mut leading);
+            }
+            match token {
+                Some((TokenKind::Identifier, type_name)) => {
+                    structs.push(self.parse_block(leading, type_name))
+                }
+                _ => {
+                    return Document {
+                        structs,
+                        trailing: leading,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Takes the next token, along with the whitespace and comments leading up to it.
+    fn next(&mut self) -> (String, Option<(TokenKind, &'a str)>) {
+        let mut trivia = String::new();
+        for (kind, slice, _) in &mut self.tokens {
+            if kind.is_trivia() {
+                trivia.push_str(slice);
+            } else {
+                return (trivia, Some((kind, slice)));
+            }
+        }
+
+        (trivia, None)
+    }
+
+    /// Looks at the next token, without taking it.
+    fn peek(&self) -> Option<TokenKind> {
+        self.tokens
+            .clone()
+            .find(|(kind, _, _)| !kind.is_trivia())
+            .map(|(kind, _, _)| kind)
+    }
+
+    fn parse_block(&mut self, leading: String, type_name: &str) -> Block {
+        let mut instance_name = None;
+        let (mut before_brace, mut token) = self.next();
+        if let Some((TokenKind::String, name)) = token {
+            instance_name = Some((before_brace, name.to_string()));
+            let (trivia, next) = self.next();
+            before_brace = trivia;
+            token = next;
+        }
+        debug_assert_eq!(token.map(|(kind, _)| kind), Some(TokenKind::BraceOpen));
+
+        let mut items: Vec<Node> = Vec::new();
+        let before_close = loop {
+            let (mut leading, token) = self.next();
+            if let Some(previous) = items.last_mut() {
+                *previous.trailing_mut() = split_trailing(&mut leading);
+            }
+            let name = match token {
+                Some((TokenKind::Identifier, name)) => name,
+                _ => break leading,
+            };
+
+            let item = match self.peek() {
+                Some(TokenKind::Assign) => {
+                    let (before_assign, _) = self.next();
+                    let (after_assign, value) = self.parse_value();
+                    Node::Property(PropertyNode {
+                        leading,
+                        name: name.to_string(),
+                        before_assign,
+                        after_assign,
+                        value,
+                        trailing: String::new(),
+                    })
+                }
+                Some(TokenKind::String) | Some(TokenKind::BraceOpen) => {
+                    Node::Struct(self.parse_block(leading, name))
+                }
+                _ => Node::Flag(FlagNode {
+                    leading,
+                    name: name.to_string(),
+                    trailing: String::new(),
+                }),
+            };
+            items.push(item);
+        };
+
+        Block {
+            leading,
+            type_name: type_name.to_string(),
+            instance_name,
+            before_brace,
+            items,
+            before_close,
+            trailing: String::new(),
+        }
+    }
+
+    /// Takes the source text of a value, along with the whitespace and comments leading up to it.
+    fn parse_value(&mut self) -> (String, String) {
+        let (leading, token) = self.next();
+        let mut value = String::new();
+        match token {
+            Some((TokenKind::String, slice)) => {
+                value.push_str(slice);
+                while self.peek() == Some(TokenKind::String) {
+                    let (trivia, token) = self.next();
+                    value.push_str(&trivia);
+                    value.extend(token.map(|(_, slice)| slice));
+                }
+            }
+            Some((TokenKind::BracketOpen, slice)) => {
+                value.push_str(slice);
+                let mut depth = 1;
+                while depth > 0 {
+                    let (trivia, token) = self.next();
+                    value.push_str(&trivia);
+                    match token {
+                        Some((kind, slice)) => {
+                            match kind {
+                                TokenKind::BracketOpen => depth += 1,
+                                TokenKind::BracketClose => depth -= 1,
+                                _ => {}
+                            }
+                            value.push_str(slice);
+                        }
+                        None => break,
+                    }
+                }
+            }
+            Some((_, slice)) => value.push_str(slice),
+            None => {}
+        }
+
+        (leading, value)
+    }
+}
+
+/// Splits off the part of `leading` that is a comment on the same line as the previous item, so
+/// that it stays with that item.
+fn split_trailing(leading: &mut String) -> String {
+    match leading.find('\n') {
+        Some(index) if !leading[..index].trim().is_empty() => {
+            let rest = leading.split_off(index);
+            std::mem::replace(leading, rest)
+        }
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,90 @@
+//! The types the tests of this crate deserialize struct blocks into.
+#![allow(dead_code)]
+
+use crate::de::{Instance, Ref, Registry};
+use serde::Deserialize;
+use serde_derive::Deserialize;
+use std::fmt;
+use std::sync::OnceLock;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename = "monster", deny_unknown_fields)]
+pub(crate) struct Monster {
+    pub(crate) instance_name: String,
+    pub(crate) hp: i32,
+    #[serde(default)]
+    pub(crate) undead: bool,
+    #[serde(default)]
+    pub(crate) drops: Vec<Ref<ItemType>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename = "item_type")]
+pub(crate) struct ItemType {
+    pub(crate) instance_name: String,
+    pub(crate) cost: i32,
+    pub(crate) weight: Option<f32>,
+    pub(crate) name: Option<String>,
+    pub(crate) symbol: Option<char>,
+    pub(crate) stackable: Option<bool>,
+    pub(crate) list: Option<Vec<u8>>,
+    #[serde(default)]
+    pub(crate) drops: Vec<Ref<ItemType>>,
+    /// The struct blocks of the types in the [`registry`], when the tests ask for them.
+    #[serde(default)]
+    pub(crate) components: Vec<Box<dyn Component>>,
+}
+
+impl Instance for ItemType {
+    fn instance_name(&self) -> &str {
+        &self.instance_name
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename = "effect")]
+pub(crate) struct Effect {
+    pub(crate) instance_name: String,
+    #[serde(default)]
+    pub(crate) power: i32,
+    pub(crate) cost: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename = "ammo")]
+pub(crate) struct Ammo {
+    pub(crate) instance_name: String,
+    pub(crate) item: Ref<ItemType>,
+}
+
+pub(crate) trait Component: fmt::Debug {
+    fn power(&self) -> i32;
+}
+
+impl Component for Effect {
+    fn power(&self) -> i32 {
+        self.power
+    }
+}
+
+impl Component for Ammo {
+    fn power(&self) -> i32 {
+        0
+    }
+}
+
+/// The types that can go into [`ItemType::components`].
+pub(crate) fn registry() -> &'static Registry<dyn Component> {
+    static REGISTRY: OnceLock<Registry<dyn Component>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        Registry::new()
+            .register("effect", |e: Effect| Box::new(e) as _)
+            .register("ammo", |a: Ammo| Box::new(a) as _)
+    })
+}
+
+impl<'de> Deserialize<'de> for Box<dyn Component> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        registry().deserialize(deserializer)
+    }
+}
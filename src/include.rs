@@ -0,0 +1,552 @@
+//! Splicing other files into a config file with `include "path"` directives.
+//!
+//! An `include` directive can appear anywhere a property, flag or struct block can, and is
+//! replaced by the contents of the files it names. Paths are resolved relative to the directory of
+//! the including file, and their file names may contain `*` and `?` wildcards, in which case all
+//! the matching files are included, ordered by path. The keyword can be changed with
+//! [`Includes::directive`], and where the files come from with a [`Loader`].
+//!
+//! The result is a single source string that can be handed to any of the deserializers. Locations
+//! within it, like those of errors, can be mapped back to the file they came from with
+//! [`Expanded::locate`].
+//!
+//! ```
+//! use serde_derive::Deserialize;
+//! use serde_tcod_config_parser::ast;
+//! use serde_tcod_config_parser::de::{Deserializer, ErrorKind};
+//! use serde_tcod_config_parser::include::Includes;
+//! use std::collections::HashMap;
+//! use std::path::PathBuf;
+//!
+//! #[derive(Deserialize)]
+//! #[serde(rename = "monster")]
+//! struct Monster {
+//!     instance_name: String,
+//!     hp: i32,
+//! }
+//!
+//! let mut files = HashMap::new();
+//! let mut add = |path: &str, source: &str| files.insert(PathBuf::from(path), source.to_string());
+//! add("data/main.cfg", "include \"monsters/*.cfg\"");
+//! add("data/monsters/orc.cfg", "monster \"Orc\" { hp = 10 }");
+//! add("data/monsters/troll.cfg", "monster \"Troll\" {\n  hp = \"lots\"\n}");
+//!
+//! let expanded = Includes::with_loader(files).load("data/main.cfg").unwrap();
+//! let mut de = Deserializer::from_file(ast::parse(expanded.source()).unwrap());
+//! let error = serde::Deserialize::deserialize(&mut de)
+//!     .map(|_: Vec<Monster>| ())
+//!     .unwrap_err();
+//! assert_eq!(error.kind(), ErrorKind::InvalidType);
+//!
+//! let location = expanded.locate_error(&error).unwrap();
+//! assert_eq!(location.file.as_deref(), Some("data/monsters/troll.cfg"));
+//! assert_eq!((location.start.line, location.start.column), (2, 8));
+//! ```
+//!
+//! [`Includes::directive`]: struct.Includes.html#method.directive
+//! [`Loader`]: trait.Loader.html
+//! [`Expanded::locate`]: struct.Expanded.html#method.locate
+use crate::de::{Error, Result};
+use crate::lexer::{Position, Span, TokenKind, Tokenizer};
+use crate::source_map::Location;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Where included files come from.
+pub trait Loader {
+    /// Reads the file at `path`.
+    fn load(&mut self, path: &Path) -> io::Result<String>;
+
+    /// The files matching `pattern`, whose components may contain `*` and `?` wildcards, ordered
+    /// by path.
+    fn glob(&mut self, pattern: &Path) -> io::Result<Vec<PathBuf>>;
+}
+
+/// A [`Loader`] reading files from the file system.
+///
+/// Wildcards only match within directories, so a pattern whose directories don't exist matches
+/// nothing, but a directory that exists and can't be read is an error.
+///
+/// [`Loader`]: trait.Loader.html
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FileSystem;
+
+impl Loader for FileSystem {
+    fn load(&mut self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn glob(&mut self, pattern: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut candidates = vec![PathBuf::new()];
+        for component in pattern.components() {
+            let component = component.as_os_str();
+            let wildcard = component.to_str().filter(|name| has_wildcards(name));
+            let mut next = Vec::new();
+            for candidate in candidates {
+                match wildcard {
+                    Some(wildcard) => {
+                        let directory = if candidate.as_os_str().is_empty() {
+                            Path::new(".")
+                        } else {
+                            &candidate
+                        };
+                        if !directory.is_dir() {
+                            continue;
+                        }
+                        for entry in fs::read_dir(directory)? {
+                            let name = entry?.file_name();
+                            if name.to_str().is_some_and(|name| matches(wildcard, name)) {
+                                next.push(candidate.join(name));
+                            }
+                        }
+                    }
+                    None => next.push(candidate.join(component)),
+                }
+            }
+            candidates = next;
+        }
+
+        candidates.retain(|candidate| candidate.is_file());
+        candidates.sort();
+        Ok(candidates)
+    }
+}
+
+/// An in-memory [`Loader`], from the paths of the files to their contents.
+///
+/// [`Loader`]: trait.Loader.html
+impl Loader for HashMap<PathBuf, String> {
+    fn load(&mut self, path: &Path) -> io::Result<String> {
+        self.get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such file"))
+    }
+
+    fn glob(&mut self, pattern: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut paths = self
+            .keys()
+            .filter(|path| matches_path(pattern, path))
+            .cloned()
+            .collect::<Vec<_>>();
+        paths.sort();
+        Ok(paths)
+    }
+}
+
+/// Expands the `include` directives of config files.
+#[derive(Debug)]
+pub struct Includes<L = FileSystem> {
+    loader: L,
+    directive: String,
+}
+
+impl Includes {
+    /// Creates an expander reading files from the file system.
+    pub fn new() -> Self {
+        Self::with_loader(FileSystem)
+    }
+}
+
+impl Default for Includes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<L: Loader> Includes<L> {
+    /// Creates an expander reading files with `loader`.
+    pub fn with_loader(loader: L) -> Self {
+        Self {
+            loader,
+            directive: "include".to_string(),
+        }
+    }
+
+    /// Sets the keyword of the directive, `include` by default.
+    pub fn directive(mut self, keyword: impl Into<String>) -> Self {
+        self.directive = keyword.into();
+        self
+    }
+
+    /// Loads the file at `path` and expands its directives, and those of the files it includes.
+    ///
+    /// Returns an [`Error::Include`] for a file that cannot be loaded, and an
+    /// [`Error::IncludeCycle`] naming all the files involved for a file that ends up including
+    /// itself. Both give the location of the directive within the including file.
+    ///
+    /// [`Error::Include`]: ../de/enum.Error.html#variant.Include
+    /// [`Error::IncludeCycle`]: ../de/enum.Error.html#variant.IncludeCycle
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<Expanded> {
+        let path = normalize(path.as_ref());
+        let source = self.loader.load(&path).map_err(|error| Error::Include {
+            path: path.display().to_string(),
+            file: None,
+            message: error.to_string(),
+            range: None,
+        })?;
+        self.expand(path, source)
+    }
+
+    /// Expands the directives of `source`, the contents of the file at `path`, and those of the
+    /// files it includes, like [`load`].
+    ///
+    /// [`load`]: #method.load
+    pub fn expand(
+        &mut self,
+        path: impl AsRef<Path>,
+        source: impl Into<String>,
+    ) -> Result<Expanded> {
+        let mut expansion = Expansion {
+            loader: &mut self.loader,
+            directive: &self.directive,
+            expanded: Expanded::default(),
+            stack: Vec::new(),
+        };
+        expansion.file(normalize(path.as_ref()), source.into())?;
+        Ok(expansion.expanded)
+    }
+}
+
+/// A config file with the files it includes spliced in.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Expanded {
+    source: String,
+    /// The paths and contents of all the files, in the order they were included.
+    files: Vec<(PathBuf, String)>,
+    segments: Vec<Segment>,
+}
+
+/// A run of the expanded source copied from one of the files.
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Segment {
+    /// Where the run starts in the expanded source.
+    start: usize,
+    /// The index of the file in [`Expanded::files`].
+    file: usize,
+    /// The part of the file that was copied.
+    span: Span,
+}
+
+impl Expanded {
+    /// The expanded source string.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The paths of all the files, starting with the including one, in the order they were
+    /// included.
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|(path, _)| path.as_path())
+    }
+
+    /// Finds where `span`, a location in the expanded source, was given in the file it came from.
+    pub fn locate(&self, span: &Span) -> Option<Location> {
+        let index = self
+            .segments
+            .iter()
+            .rposition(|segment| segment.start <= span.start)?;
+        let segment = &self.segments[index];
+        let (path, source) = &self.files[segment.file];
+
+        let start = segment.span.start + (span.start - segment.start).min(segment.span.len());
+        let end = (start + span.len()).min(segment.span.end);
+        Some(Location {
+            file: Some(path.display().to_string()),
+            span: start..end,
+            start: Position::of(source, start),
+            end: Position::of(source, end),
+        })
+    }
+
+    /// Finds where an error in the expanded source was encountered, if the error has a location.
+    pub fn locate_error(&self, error: &Error) -> Option<Location> {
+        self.locate(&error.range()?)
+    }
+}
+
+struct Expansion<'e, L> {
+    loader: &'e mut L,
+    directive: &'e str,
+    expanded: Expanded,
+    /// The files being expanded, each included by the one before it.
+    stack: Vec<PathBuf>,
+}
+
+impl<'e, L: Loader> Expansion<'e, L> {
+    /// Appends `source`, the contents of the file at `path`, with its directives expanded.
+    fn file(&mut self, path: PathBuf, source: String) -> Result<()> {
+        let directives = directives(&source, self.directive);
+        let index = self.expanded.files.len();
+        self.expanded.files.push((path.clone(), source.clone()));
+        self.stack.push(path.clone());
+
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut position = 0;
+        for (span, target) in directives {
+            self.copy(index, position..span.start);
+            position = span.end;
+
+            let pattern = normalize(&directory.join(target));
+            let error = |included: &Path, message: String| Error::Include {
+                path: included.display().to_string(),
+                file: Some(path.display().to_string()),
+                message,
+                range: Some(span.clone()),
+            };
+            let paths = if has_wildcards(target) {
+                self.loader
+                    .glob(&pattern)
+                    .map_err(|e| error(&pattern, e.to_string()))?
+            } else {
+                vec![pattern.clone()]
+            };
+
+            for included in paths {
+                if let Some(start) = self.stack.iter().position(|file| *file == included) {
+                    let mut chain = self.stack[start..]
+                        .iter()
+                        .map(|file| file.display().to_string())
+                        .collect::<Vec<_>>();
+                    chain.push(included.display().to_string());
+                    return Err(Error::IncludeCycle {
+                        chain,
+                        file: path.display().to_string(),
+                        range: span,
+                    });
+                }
+
+                let source = self
+                    .loader
+                    .load(&included)
+                    .map_err(|e| error(&included, e.to_string()))?;
+                self.expanded.source.push('\n');
+                self.file(included, source)?;
+                self.expanded.source.push('\n');
+            }
+        }
+        self.copy(index, position..source.len());
+
+        self.stack.pop();
+        Ok(())
+    }
+
+    /// Appends `span` of the file at `index` to the expanded source.
+    fn copy(&mut self, index: usize, span: Span) {
+        let expanded = &mut self.expanded;
+        expanded.segments.push(Segment {
+            start: expanded.source.len(),
+            file: index,
+            span: span.clone(),
+        });
+        expanded.source.push_str(&expanded.files[index].1[span]);
+    }
+}
+
+/// Finds the `directive "path"` directives in `source`, with their locations and the paths they
+/// name. A directive keyword followed by a string and a `{` is a struct block instead.
+fn directives<'s>(source: &'s str, directive: &str) -> Vec<(Span, &'s str)> {
+    let tokens = Tokenizer::new(source).collect::<Vec<_>>();
+    let mut directives = Vec::new();
+    for (index, window) in tokens.windows(2).enumerate() {
+        match window {
+            [(TokenKind::Identifier, keyword, start), (TokenKind::String, path, end)]
+                if *keyword == directive =>
+            {
+                let block = matches!(tokens.get(index + 2), Some((TokenKind::BraceOpen, ..)));
+                let value = index > 0 && tokens[index - 1].0 == TokenKind::Assign;
+                if !block && !value {
+                    directives.push((start.start..end.end, &path[1..path.len() - 1]));
+                }
+            }
+            _ => {}
+        }
+    }
+    directives
+}
+
+/// Removes the `.` and `..` components of `path` that can be removed without looking at the file
+/// system, so that the same file is always given by the same path.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn has_wildcards(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Whether every component of `path` matches the one of `pattern`.
+fn matches_path(pattern: &Path, path: &Path) -> bool {
+    let mut patterns = pattern.components();
+    let mut components = path.components();
+    loop {
+        match (patterns.next(), components.next()) {
+            (None, None) => return true,
+            (Some(pattern), Some(component)) => {
+                let pattern = pattern.as_os_str().to_string_lossy();
+                let component = component.as_os_str().to_string_lossy();
+                if !matches(&pattern, &component) {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any run of characters and `?` any single
+/// character.
+fn matches(pattern: &str, name: &str) -> bool {
+    let mut pattern_chars = pattern.chars();
+    match pattern_chars.next() {
+        None => name.is_empty(),
+        Some('*') => {
+            let rest = pattern_chars.as_str();
+            name.char_indices()
+                .map(|(index, _)| index)
+                .chain(Some(name.len()))
+                .any(|index| matches(rest, &name[index..]))
+        }
+        Some(c) => {
+            let mut name_chars = name.chars();
+            match name_chars.next() {
+                Some(n) if c == '?' || c == n => {
+                    matches(pattern_chars.as_str(), name_chars.as_str())
+                }
+                _ => false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches, FileSystem, Includes, Loader};
+    use crate::ast;
+    use crate::de::Error;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn files(files: &[(&str, &str)]) -> HashMap<PathBuf, String> {
+        files
+            .iter()
+            .map(|(path, source)| (PathBuf::from(path), source.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*.cfg", "orc.cfg"));
+        assert!(matches("o?c.*", "orc.cfg"));
+        assert!(matches("*", ""));
+        assert!(!matches("*.cfg", "orc.txt"));
+        assert!(!matches("?", ""));
+    }
+
+    #[test]
+    fn nested_files() {
+        let loader = files(&[
+            (
+                "main.cfg",
+                "world {\n  include \"monsters/*.cfg\"\n}\ninclude \"items.cfg\"",
+            ),
+            ("monsters/b.cfg", "monster \"B\" { }"),
+            (
+                "monsters/a.cfg",
+                "monster \"A\" { include \"../items.cfg\" }",
+            ),
+            ("items.cfg", "include \"Sword\" { }"),
+        ]);
+        let expanded = Includes::with_loader(loader).load("main.cfg").unwrap();
+        let file = ast::parse(expanded.source()).unwrap();
+        let world = &file.structs[0];
+        assert_eq!(world.items.len(), 2);
+        assert_eq!(file.structs[1].type_name, "include");
+        assert_eq!(
+            expanded
+                .files()
+                .map(|path| path.to_str().unwrap())
+                .collect::<Vec<_>>(),
+            [
+                "main.cfg",
+                "monsters/a.cfg",
+                "items.cfg",
+                "monsters/b.cfg",
+                "items.cfg"
+            ]
+        );
+
+        let b = expanded.source().find("\"B\"").unwrap();
+        let location = expanded.locate(&(b..b + 3)).unwrap();
+        assert_eq!(location.file.as_deref(), Some("monsters/b.cfg"));
+        assert_eq!(location.span, 8..11);
+
+        let loader = files(&[
+            ("main.cfg", "use \"monsters/a.cfg\""),
+            ("monsters/a.cfg", "use \"../main.cfg\""),
+        ]);
+        let mut includes = Includes::with_loader(loader).directive("use");
+        match includes.load("main.cfg").unwrap_err() {
+            Error::IncludeCycle { chain, file, range } => {
+                assert_eq!(chain, ["main.cfg", "monsters/a.cfg", "main.cfg"]);
+                assert_eq!(file, "monsters/a.cfg");
+                assert_eq!(range, 0..17);
+            }
+            error => panic!("unexpected error: {:?}", error),
+        }
+
+        let loader = files(&[("main.cfg", "hp = 1\ninclude \"missing.cfg\"")]);
+        assert!(matches!(
+            Includes::with_loader(loader).load("main.cfg"),
+            Err(Error::Include { path, file, range, .. })
+                if path == "missing.cfg"
+                    && file.as_deref() == Some("main.cfg")
+                    && range == Some(7..28)
+        ));
+    }
+
+    #[test]
+    fn file_system() {
+        /// Removes the directory once the test is done, whether it passes or not.
+        struct TempDir(PathBuf);
+
+        impl Drop for TempDir {
+            fn drop(&mut self) {
+                let _ = fs::remove_dir_all(&self.0);
+            }
+        }
+
+        let name = format!("serde_tcod_config_parser_include_{}", std::process::id());
+        let temp_dir = TempDir(std::env::temp_dir().join(name));
+        let directory = temp_dir.0.clone();
+        fs::create_dir_all(directory.join("monsters")).unwrap();
+        fs::write(directory.join("monsters/orc.cfg"), "monster \"Orc\" { }").unwrap();
+        fs::write(directory.join("monsters/notes.txt"), "").unwrap();
+        fs::write(directory.join("main.cfg"), "include \"monsters/*.cfg\"").unwrap();
+
+        let paths = FileSystem.glob(&directory.join("m*/*.cfg")).unwrap();
+        assert_eq!(paths, [directory.join("monsters/orc.cfg")]);
+        let expanded = Includes::new().load(directory.join("main.cfg")).unwrap();
+        assert_eq!(expanded.source().trim(), "monster \"Orc\" { }");
+
+        drop(temp_dir);
+        assert!(!directory.exists());
+    }
+}
@@ -1,7 +1,28 @@
+//! The tokenizer used by the deserializer, exposed so that tools like syntax highlighters and
+//! formatters can tokenize libtcod config files exactly like the deserializer does.
+//!
+//! ```
+//! use serde_tcod_config_parser::lexer::{TokenKind, Tokenizer};
+//!
+//! let tokens = Tokenizer::new("cost = 10 // in gold")
+//!     .map(|(kind, slice, _span)| (kind, slice))
+//!     .collect::<Vec<_>>();
+//!
+//! assert_eq!(
+//!     tokens,
+//!     vec![
+//!         (TokenKind::Identifier, "cost"),
+//!         (TokenKind::Assign, "="),
+//!         (TokenKind::Integer, "10"),
+//!     ]
+//! );
+//! ```
 use logos::internal::LexerInternal;
 use logos::{Lexer, Logos, Source};
+use std::ops::Range;
 
 #[derive(Debug, PartialEq, Clone, Copy, Logos)]
+#[logos(trivia = "[ \\t\\n\\r\\f]")]
 pub(crate) enum Token {
     #[end]
     EndOfProgram,
@@ -88,11 +109,421 @@ fn ignore_comments<'source, Src: Source<'source>>(lex: &mut Lexer<Token, Src>) {
     lex.advance();
 }
 
+/// A location in the source string, as a range of byte offsets.
+pub type Span = Range<usize>;
+
+/// A location in the source string, as a line and column, both starting at 1. Columns are counted
+/// in characters, not bytes.
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct Position {
+    /// The line, starting at 1.
+    pub line: usize,
+    /// The column, starting at 1.
+    pub column: usize,
+}
+
+impl Position {
+    /// Finds the line and column of the byte `offset` in `source`.
+    pub fn of(source: &str, offset: usize) -> Self {
+        let before = &source[..offset.min(source.len())];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+/// The kind of a token produced by a [`Tokenizer`].
+///
+/// [`Tokenizer`]: struct.Tokenizer.html
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[non_exhaustive]
+pub enum TokenKind {
+    /// A string, e.g. `"Orc"`.
+    String,
+    /// A char, e.g. `'a'` or `'\n'`.
+    Char,
+    /// A floating point number, e.g. `1.5`.
+    Float,
+    /// A hexadecimal integer, e.g. `0xFF`.
+    Hex,
+    /// A decimal integer, e.g. `42`.
+    Integer,
+    /// An identifier, used for struct type names, property names and flags.
+    Identifier,
+    /// A color, e.g. `#FF00FF`.
+    Color,
+    /// `{`
+    BraceOpen,
+    /// `}`
+    BraceClose,
+    /// `=`
+    Assign,
+    /// `,`
+    Comma,
+    /// `[`
+    BracketOpen,
+    /// `]`
+    BracketClose,
+    /// A run of whitespace. Only produced when trivia is requested.
+    Whitespace,
+    /// A `// ...` comment, not including the line break. Only produced when trivia is requested.
+    LineComment,
+    /// A (possibly nested) `/* ... */` comment. Only produced when trivia is requested.
+    BlockComment,
+    /// A `/* ...` comment that is never closed.
+    UnclosedBlockComment,
+    /// Something that is not a valid token.
+    Unexpected,
+}
+
+impl TokenKind {
+    /// A stable, human readable name for the token kind, e.g. for use as a CSS class name.
+    pub fn name(self) -> &'static str {
+        match self {
+            TokenKind::String => "string",
+            TokenKind::Char => "char",
+            TokenKind::Float => "float",
+            TokenKind::Hex => "hex",
+            TokenKind::Integer => "integer",
+            TokenKind::Identifier => "identifier",
+            TokenKind::Color => "color",
+            TokenKind::BraceOpen => "brace-open",
+            TokenKind::BraceClose => "brace-close",
+            TokenKind::Assign => "assign",
+            TokenKind::Comma => "comma",
+            TokenKind::BracketOpen => "bracket-open",
+            TokenKind::BracketClose => "bracket-close",
+            TokenKind::Whitespace => "whitespace",
+            TokenKind::LineComment => "line-comment",
+            TokenKind::BlockComment => "block-comment",
+            TokenKind::UnclosedBlockComment => "unclosed-block-comment",
+            TokenKind::Unexpected => "unexpected",
+        }
+    }
+
+    /// Whether this kind of token is whitespace or a comment.
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment
+        )
+    }
+}
+
+impl From<Token> for TokenKind {
+    fn from(token: Token) -> Self {
+        match token {
+            Token::Text => TokenKind::String,
+            Token::Char => TokenKind::Char,
+            Token::Float => TokenKind::Float,
+            Token::Hex => TokenKind::Hex,
+            Token::Integer => TokenKind::Integer,
+            Token::Identifier => TokenKind::Identifier,
+            Token::Color => TokenKind::Color,
+            Token::BraceOpen => TokenKind::BraceOpen,
+            Token::BraceClose => TokenKind::BraceClose,
+            Token::Assign => TokenKind::Assign,
+            Token::Comma => TokenKind::Comma,
+            Token::BracketOpen => TokenKind::BracketOpen,
+            Token::BracketClose => TokenKind::BracketClose,
+            Token::UnclosedMultilineComment => TokenKind::UnclosedBlockComment,
+            Token::EndOfProgram | Token::Unexpected => TokenKind::Unexpected,
+        }
+    }
+}
+
+/// An iterator over the tokens of a libtcod config file, yielding the kind, the source slice and
+/// the location of each token.
+///
+/// By default, whitespace and comments are skipped, just like the deserializer does. Use
+/// [`with_trivia`] to have them produced as well, in which case the slices of all the tokens
+/// concatenated reproduce the source string.
+///
+/// [`with_trivia`]: #method.with_trivia
+#[derive(Clone)]
+pub struct Tokenizer<'source> {
+    lexer: Lexer<Token, &'source str>,
+    trivia: bool,
+    position: usize,
+}
+
+impl<'source> Tokenizer<'source> {
+    /// Creates a tokenizer for `source`.
+    pub fn new(source: &'source str) -> Self {
+        Self {
+            lexer: Token::lexer(source),
+            trivia: false,
+            position: 0,
+        }
+    }
+
+    /// Sets whether whitespace and comments are produced.
+    pub fn with_trivia(mut self, trivia: bool) -> Self {
+        self.trivia = trivia;
+        self
+    }
+}
+
+impl<'source> Iterator for Tokenizer<'source> {
+    type Item = (TokenKind, &'source str, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Skip what is left of a character an error token was widened to the end of
+        while self.lexer.token != Token::EndOfProgram && self.lexer.range().start < self.position {
+            self.lexer.advance();
+        }
+
+        let source = self.lexer.source;
+        let start = if self.lexer.token == Token::EndOfProgram {
+            source.len()
+        } else {
+            self.lexer.range().start
+        };
+
+        if self.trivia && self.position < start {
+            let (kind, span) = next_trivia(source, self.position, start);
+            self.position = span.end;
+            return Some((kind, &source[span.clone()], span));
+        }
+
+        if self.lexer.token == Token::EndOfProgram {
+            return None;
+        }
+
+        let span = token_range(&self.lexer);
+        let result = (self.lexer.token.into(), &source[span.clone()], span.clone());
+        self.position = span.end;
+        self.lexer.advance();
+
+        Some(result)
+    }
+}
+
+/// Finds the first whitespace run or comment in `source[start..end]`, which is known to contain
+/// only whitespace and comments.
+pub(crate) fn next_trivia(source: &str, start: usize, end: usize) -> (TokenKind, Span) {
+    let bytes = &source.as_bytes()[..end];
+    match &bytes[start..] {
+        [b'/', b'/', ..] => {
+            let length = bytes[start..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .unwrap_or(end - start);
+            (TokenKind::LineComment, start..start + length)
+        }
+        [b'/', b'*', ..] => {
+            let mut level = 0;
+            let mut position = start;
+            while position < end {
+                match &bytes[position..] {
+                    [b'/', b'*', ..] => {
+                        level += 1;
+                        position += 2;
+                    }
+                    [b'*', b'/', ..] => {
+                        level -= 1;
+                        position += 2;
+                        if level == 0 {
+                            break;
+                        }
+                    }
+                    _ => position += 1,
+                }
+            }
+            (TokenKind::BlockComment, start..position)
+        }
+        _ => {
+            let mut position = start;
+            while position < end && !bytes[position..].starts_with(b"/") {
+                position += 1;
+            }
+            (TokenKind::Whitespace, start..position.max(start + 1))
+        }
+    }
+}
+
+/// The problems that can be encountered while tokenizing a libtcod config file.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[non_exhaustive]
+pub enum LexicalError {
+    /// A string is missing its closing `"`.
+    UnterminatedString,
+    /// A char is missing its closing `'`.
+    UnterminatedChar,
+    /// A char contains more or less than a single character, e.g. `'ab'`.
+    MalformedChar,
+    /// A char contains an escape sequence that is not supported, e.g. `'\q'`.
+    InvalidEscapeSequence,
+    /// A color is not made up of exactly six hexadecimal digits, e.g. `#FF00F`.
+    MalformedColor,
+    /// A `/*` comment is never closed.
+    UnclosedComment,
+    /// A character that cannot start any token.
+    StrayCharacter,
+}
+
+impl std::fmt::Display for LexicalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            LexicalError::UnterminatedString => "unterminated string",
+            LexicalError::UnterminatedChar => "unterminated char",
+            LexicalError::MalformedChar => "char must contain exactly one character",
+            LexicalError::InvalidEscapeSequence => "invalid escape sequence",
+            LexicalError::MalformedColor => "malformed color, expected #RRGGBB",
+            LexicalError::UnclosedComment => "unclosed comment",
+            LexicalError::StrayCharacter => "stray character",
+        })
+    }
+}
+
+/// The location of the current token of `lexer`. Error tokens can end in the middle of a
+/// character, so they are widened to the end of it.
+pub(crate) fn token_range(lexer: &Lexer<Token, &str>) -> Span {
+    let range = lexer.range();
+    let end = (range.end..lexer.source.len())
+        .find(|&end| lexer.source.is_char_boundary(end))
+        .unwrap_or(lexer.source.len());
+    range.start..end
+}
+
+/// Works out what went wrong if the lexer is at an error token, and where.
+///
+/// The location returned covers the problematic part of the source, e.g. all of an unterminated
+/// string, or just the opening `/*` of an unclosed comment.
+pub(crate) fn lexical_error(lexer: &Lexer<Token, &str>) -> Option<(LexicalError, Span)> {
+    let start = lexer.range().start;
+    let rest = &lexer.source[start..];
+    let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+
+    let (error, length) = match lexer.token {
+        Token::UnclosedMultilineComment => (LexicalError::UnclosedComment, 2),
+        Token::Unexpected => match rest.chars().next()? {
+            '"' => (LexicalError::UnterminatedString, rest.len()),
+            '\'' => {
+                let content = &line[1..];
+                // Skip past the escaped character, which might be a quote
+                let skip = match content.chars().take(2).collect::<Vec<_>>()[..] {
+                    ['\\', c] => 1 + c.len_utf8(),
+                    _ => 0,
+                };
+                match content[skip..].find('\'') {
+                    Some(end) if skip > 0 => (LexicalError::InvalidEscapeSequence, skip + end + 2),
+                    Some(end) => (LexicalError::MalformedChar, end + 2),
+                    None => (LexicalError::UnterminatedChar, line.trim_end().len()),
+                }
+            }
+            '#' => {
+                let length = line[1..]
+                    .find(|c: char| !c.is_ascii_alphanumeric())
+                    .unwrap_or(line.len() - 1);
+                (LexicalError::MalformedColor, length + 1)
+            }
+            c => (LexicalError::StrayCharacter, c.len_utf8()),
+        },
+        _ => return None,
+    };
+
+    Some((error, start..start + length))
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Token;
+    use super::{lexical_error, LexicalError, Token, TokenKind, Tokenizer};
     use logos::Logos;
 
+    #[test]
+    fn lexical_errors() {
+        let error = |source| lexical_error(&Token::lexer(source));
+
+        assert_eq!(error("a"), None);
+        assert_eq!(
+            error("\"abc\n = 1"),
+            Some((LexicalError::UnterminatedString, 0..9))
+        );
+        assert_eq!(
+            error("'a\r\n"),
+            Some((LexicalError::UnterminatedChar, 0..2))
+        );
+        assert_eq!(error("'ab' x"), Some((LexicalError::MalformedChar, 0..4)));
+        assert_eq!(error("'' x"), Some((LexicalError::MalformedChar, 0..2)));
+        assert_eq!(
+            error("'\\q' x"),
+            Some((LexicalError::InvalidEscapeSequence, 0..4))
+        );
+        assert_eq!(
+            error("'\\x' x"),
+            Some((LexicalError::InvalidEscapeSequence, 0..4))
+        );
+        assert_eq!(
+            error("#FF00F x"),
+            Some((LexicalError::MalformedColor, 0..6))
+        );
+        assert_eq!(
+            error("/* a /* b */"),
+            Some((LexicalError::UnclosedComment, 0..2))
+        );
+        assert_eq!(error("\u{e9}"), Some((LexicalError::StrayCharacter, 0..2)));
+    }
+
+    #[test]
+    fn tokenizer_trivia() {
+        let source = "a = 1 // one\n/* two /* three */ */ b\r\n";
+        let tokens = Tokenizer::new(source)
+            .with_trivia(true)
+            .map(|(kind, slice, span)| {
+                assert_eq!(&source[span], slice);
+                (kind, slice)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            vec![
+                (TokenKind::Identifier, "a"),
+                (TokenKind::Whitespace, " "),
+                (TokenKind::Assign, "="),
+                (TokenKind::Whitespace, " "),
+                (TokenKind::Integer, "1"),
+                (TokenKind::Whitespace, " "),
+                (TokenKind::LineComment, "// one"),
+                (TokenKind::Whitespace, "\n"),
+                (TokenKind::BlockComment, "/* two /* three */ */"),
+                (TokenKind::Whitespace, " "),
+                (TokenKind::Identifier, "b"),
+                (TokenKind::Whitespace, "\r\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenizer_stray_characters() {
+        for source in ["a \u{e9} b", "'\u{e9}'"] {
+            let slices = Tokenizer::new(source)
+                .with_trivia(true)
+                .map(|(_, slice, _)| slice)
+                .collect::<String>();
+            assert_eq!(slices, source);
+        }
+
+        let tokens = Tokenizer::new("\u{e9}").collect::<Vec<_>>();
+        assert_eq!(tokens, vec![(TokenKind::Unexpected, "\u{e9}", 0..2)]);
+    }
+
+    #[test]
+    fn tokenizer_unclosed_comment() {
+        let tokens = Tokenizer::new("a /* b").collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            vec![
+                (TokenKind::Identifier, "a", 0..1),
+                (TokenKind::UnclosedBlockComment, "/* b", 2..6),
+            ]
+        );
+    }
+
     #[test]
     fn char_hex() {
         let sut = Token::lexer("'\\x9F' ");
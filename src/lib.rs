@@ -20,7 +20,8 @@
 //! ## No support for dynamic declarations
 //! The original format allows declaring structs and fields that don't exist in the actual type
 //! declarations being deserialized. I decided I didn't need this for my own needs, and so this
-//! feature is missing.
+//! feature is missing from the deserializer. Files can however be checked against declarations
+//! made at runtime with the [`schema`] module.
 //!
 //! ## No support for arbitrary order of contained structs
 //! Because the original parser was event-driven, the order that things appear in the file is mostly
@@ -76,6 +77,8 @@
 //!     }
 //! }
 //! ```
+//! If the order cannot be changed, the event-driven [`listener`] API handles such files just like
+//! the original parser did.
 //!
 //! ## No support for libtcod-specific types
 //!
@@ -86,6 +89,23 @@
 //! [`logos`]: https://crates.io/crates/logos
 //! [`serde`]: https://crates.io/crates/serde
 //! [`Deserializer`]: de/struct.Deserializer.html
+//! [`listener`]: listener/index.html
+//! [`schema`]: schema/index.html
+#[macro_use]
+mod macros;
+
+pub mod ast;
+pub mod config;
 pub mod de;
+pub mod document;
+pub mod include;
+pub mod lexer;
+pub mod listener;
+pub mod schema;
+pub mod source_map;
 
-mod lexer;
+#[cfg(test)]
+mod fixtures;
+mod path;
+#[cfg(test)]
+mod proptests;
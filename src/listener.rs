@@ -0,0 +1,194 @@
+//! Event-driven parsing, like libtcod's `ITCODParserListener`.
+//!
+//! Instead of building Rust values or a syntax tree, [`run`] reports every struct block, flag and
+//! property to a [`Listener`] as soon as it is parsed, in the order they appear in the file. This
+//! makes it easy to port code written against libtcod's listener interface, and lets huge files
+//! be processed without holding all of them in memory.
+//!
+//! ```
+//! use serde_tcod_config_parser::ast::{Value, ValueKind};
+//! use serde_tcod_config_parser::lexer::Span;
+//! use serde_tcod_config_parser::listener::{self, Listener};
+//!
+//! #[derive(Default)]
+//! struct TotalCost {
+//!     total: i64,
+//! }
+//!
+//! impl Listener for TotalCost {
+//!     fn parser_property(&mut self, name: &str, value: &Value<'_>, _span: Span) -> bool {
+//!         if let ("cost", ValueKind::Integer(cost)) = (name, &value.kind) {
+//!             self.total += cost;
+//!         }
+//!         true
+//!     }
+//! }
+//!
+//! let mut listener = TotalCost::default();
+//! let source = r#"item_type "Sword" { cost = 10 } item_type "Bow" { cost = 5 }"#;
+//! listener::run(source, &mut listener).unwrap();
+//! assert_eq!(listener.total, 15);
+//! ```
+//!
+//! [`run`]: fn.run.html
+//! [`Listener`]: trait.Listener.html
+use crate::ast::parser::Parser;
+use crate::ast::Value;
+use crate::de::{Error, Result};
+use crate::lexer::Span;
+
+/// Receives the events of [`run`]. Every method has a default implementation that does nothing,
+/// so only the interesting ones need to be implemented.
+///
+/// Like in libtcod, the methods returning `bool` can return `false` to stop parsing.
+///
+/// [`run`]: fn.run.html
+pub trait Listener {
+    /// A struct block starts, e.g. `item_type "Sword" {`. `span` is the location of the type
+    /// name.
+    fn parser_new_struct(
+        &mut self,
+        type_name: &str,
+        instance_name: Option<&str>,
+        span: Span,
+    ) -> bool {
+        let _ = (type_name, instance_name, span);
+        true
+    }
+
+    /// A flag was given, e.g. `stackable`.
+    fn parser_flag(&mut self, name: &str, span: Span) -> bool {
+        let _ = (name, span);
+        true
+    }
+
+    /// A property was given, e.g. `cost = 10`. `span` is the location of the whole property.
+    fn parser_property(&mut self, name: &str, value: &Value<'_>, span: Span) -> bool {
+        let _ = (name, value, span);
+        true
+    }
+
+    /// The struct block started by the matching [`parser_new_struct`] ends. `span` is the location
+    /// of the whole struct block.
+    ///
+    /// [`parser_new_struct`]: #method.parser_new_struct
+    fn parser_end_struct(
+        &mut self,
+        type_name: &str,
+        instance_name: Option<&str>,
+        span: Span,
+    ) -> bool {
+        let _ = (type_name, instance_name, span);
+        true
+    }
+
+    /// The file could not be parsed. Parsing stops after this.
+    fn error(&mut self, error: &Error) {
+        let _ = error;
+    }
+}
+
+/// Parses `source`, reporting everything in it to `listener` as it goes.
+///
+/// Returns the error that stopped parsing, after reporting it to [`Listener::error`], if any.
+/// Stopping because the listener asked to is not an error.
+///
+/// [`Listener::error`]: trait.Listener.html#method.error
+pub fn run(source: &str, listener: &mut impl Listener) -> Result<()> {
+    match Parser::new(source).run(listener) {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            listener.error(&error);
+            Err(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, Listener};
+    use crate::ast::{Value, ValueKind};
+    use crate::de::Error;
+    use crate::lexer::Span;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+        stop_at: Option<&'static str>,
+        errors: usize,
+    }
+
+    impl Recorder {
+        fn record(&mut self, event: String) -> bool {
+            let keep_going = self.stop_at != Some(event.as_str());
+            self.events.push(event);
+            keep_going
+        }
+    }
+
+    impl Listener for Recorder {
+        fn parser_new_struct(&mut self, type_name: &str, name: Option<&str>, _: Span) -> bool {
+            self.record(format!("new {} {:?}", type_name, name))
+        }
+
+        fn parser_flag(&mut self, name: &str, span: Span) -> bool {
+            self.record(format!("flag {} {:?}", name, span))
+        }
+
+        fn parser_property(&mut self, name: &str, value: &Value<'_>, span: Span) -> bool {
+            match value.kind {
+                ValueKind::Integer(value) => {
+                    self.record(format!("{} = {} {:?}", name, value, span))
+                }
+                _ => self.record(format!("{} = ?", name)),
+            }
+        }
+
+        fn parser_end_struct(&mut self, type_name: &str, _: Option<&str>, span: Span) -> bool {
+            self.record(format!("end {} {:?}", type_name, span))
+        }
+
+        fn error(&mut self, _: &Error) {
+            self.errors += 1;
+        }
+    }
+
+    #[test]
+    fn events() {
+        let source = "item_type \"Sword\" { cost = 10 effect { stackable } }";
+        let mut recorder = Recorder::default();
+        run(source, &mut recorder).unwrap();
+        assert_eq!(
+            recorder.events,
+            vec![
+                "new item_type Some(\"Sword\")",
+                "cost = 10 20..29",
+                "new effect None",
+                "flag stackable 39..48",
+                "end effect 30..50",
+                "end item_type 0..52",
+            ]
+        );
+
+        let mut recorder = Recorder {
+            stop_at: Some("new effect None"),
+            ..Recorder::default()
+        };
+        run(source, &mut recorder).unwrap();
+        assert_eq!(recorder.events.len(), 3);
+
+        let mut recorder = Recorder::default();
+        assert!(run("item_type { cost = }", &mut recorder).is_err());
+        assert_eq!(recorder.events, vec!["new item_type None"]);
+        assert_eq!(recorder.errors, 1);
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let mut recorder = Recorder::default();
+        let error = run(&"a { ".repeat(100_000), &mut recorder).unwrap_err();
+        assert!(matches!(error, Error::NestingTooDeep { .. }));
+        assert_eq!(recorder.events.len(), crate::de::MAX_DEPTH);
+        assert_eq!(recorder.errors, 1);
+    }
+}
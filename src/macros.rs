@@ -0,0 +1,13 @@
+macro_rules! unexpected_token {
+    ($l: expr, $e: expr) => {
+        Err(match $crate::lexer::lexical_error(&$l) {
+            Some((error, range)) => $crate::de::Error::Lexical { error, range },
+            None => $crate::de::Error::UnexpectedToken {
+                value: $l.source[$crate::lexer::token_range(&$l)].to_string(),
+                token_type: format!("{:?}", $l.token),
+                range: $crate::lexer::token_range(&$l),
+                expected: $e,
+            },
+        })
+    };
+}
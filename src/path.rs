@@ -0,0 +1,64 @@
+//! Paths that address struct blocks and properties, e.g. `item_type#Sword.effect.damage`.
+//!
+//! A path is a list of segments separated by `.`. Every segment but the last names a struct
+//! block by its type name, optionally followed by `#` and an instance name to pick a specific
+//! instance. Without an instance name, the first struct block of the given type is used. The last
+//! segment names either a property, a flag or a struct block.
+
+/// A single segment of a path.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) struct Segment<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) instance_name: Option<&'a str>,
+}
+
+impl<'a> Segment<'a> {
+    /// Whether this segment addresses a struct block with the given type and instance name.
+    pub(crate) fn matches(&self, type_name: &str, instance_name: Option<&str>) -> bool {
+        self.name == type_name
+            && (self.instance_name.is_none() || self.instance_name == instance_name)
+    }
+}
+
+/// Splits a path into its segments. An empty path has no segments.
+pub(crate) fn segments(path: &str) -> Vec<Segment<'_>> {
+    if path.is_empty() {
+        return Vec::new();
+    }
+
+    path.split('.')
+        .map(|segment| match segment.find('#') {
+            Some(index) => Segment {
+                name: &segment[..index],
+                instance_name: Some(&segment[index + 1..]),
+            },
+            None => Segment {
+                name: segment,
+                instance_name: None,
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{segments, Segment};
+
+    #[test]
+    fn split_segments() {
+        assert_eq!(segments(""), vec![]);
+        assert_eq!(
+            segments("item_type#Sword.cost"),
+            vec![
+                Segment {
+                    name: "item_type",
+                    instance_name: Some("Sword"),
+                },
+                Segment {
+                    name: "cost",
+                    instance_name: None,
+                },
+            ]
+        );
+    }
+}
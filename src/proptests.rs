@@ -0,0 +1,148 @@
+//! Checks that no input, however malformed, makes any of the entry points of this crate panic.
+//!
+//! Inputs are mostly built from streams of tokens, both valid and broken ones, so that they get
+//! past the lexer and exercise the parser and the deserializers, rather than failing on the first
+//! character like arbitrary strings tend to.
+use crate::ast;
+use crate::de::{Deserializer, Error};
+use crate::document::Document;
+use crate::fixtures::{registry, ItemType};
+use crate::lexer::Tokenizer;
+use crate::listener::{self, Listener};
+use proptest::prelude::*;
+use serde::Deserialize;
+use serde_derive::Deserialize;
+use std::collections::HashMap;
+
+/// Deserialized with `deserialize_any`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+#[allow(dead_code)]
+enum Any {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    Char(char),
+    String(String),
+    List(Vec<Any>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename = "item_type")]
+#[allow(dead_code)]
+struct AnyItemType {
+    instance_name: String,
+    cost: Option<Any>,
+    name: Option<Any>,
+    list: Option<Any>,
+    #[serde(flatten)]
+    rest: HashMap<String, Any>,
+}
+
+struct Ignore;
+
+impl Listener for Ignore {}
+
+fn token() -> impl Strategy<Value = String> {
+    prop_oneof![
+        prop::sample::select(vec![
+            "item_type",
+            "effect",
+            "ammo",
+            "power",
+            "item",
+            "instance_name",
+            "cost",
+            "weight",
+            "name",
+            "symbol",
+            "stackable",
+            "list",
+            "unknown",
+        ])
+        .prop_map(str::to_string),
+        "\"[a-z \\n]{0,5}\"",
+        "\"[a-z]{0,3}",
+        "'([a-z]|\\\\[ntrq\\\\'\"]|\\\\x[0-9a-fA-F]{1,6}|\\\\[0-7]{1,5}|[a-z]{2})'",
+        "-?[0-9]{1,25}",
+        "-?0x[0-9a-fA-F]{1,20}",
+        "-?[0-9]{0,3}\\.[0-9]{0,3}(e-?[0-9]{1,4})?",
+        "#[0-9a-fA-F]{0,7}",
+        prop::sample::select(vec![
+            "{", "}", "[", "]", "=", ",", "//x\n", "/*x*/", "/*", "@", "\u{e9}", "'",
+        ])
+        .prop_map(str::to_string),
+    ]
+}
+
+fn token_stream() -> impl Strategy<Value = String> {
+    prop::collection::vec(token(), 0..40).prop_map(|tokens| tokens.join(" "))
+}
+
+/// Feeds `source` to every entry point, ignoring the results.
+fn check(source: &str) {
+    let _ = Tokenizer::new(source).with_trivia(true).count();
+    let _ = ast::parse(source);
+    let _ = ast::parse_recovering(source);
+    let _ = listener::run(source, &mut Ignore);
+    let _ = source
+        .parse::<Document>()
+        .map(|document| document.to_string());
+
+    let _ = Deserializer::from_str::<ItemType>(source);
+    let _ = Deserializer::from_str::<Vec<ItemType>>(source);
+    let _ = Deserializer::from_str::<AnyItemType>(source);
+    let _ = Deserializer::from_str_recovering::<Vec<ItemType>>(source);
+    let _ = ItemType::deserialize(
+        &mut Deserializer::new(source).registry_field("components", registry()),
+    );
+    if let Ok(file) = ast::parse(source) {
+        let _ = ItemType::deserialize(&mut Deserializer::from_file(file.clone()));
+        let _ = ItemType::deserialize(
+            &mut Deserializer::from_file(file.clone()).registry_field("components", registry()),
+        );
+        let _ = AnyItemType::deserialize(&mut Deserializer::from_file(file));
+    }
+}
+
+proptest! {
+    #[test]
+    fn token_streams_do_not_panic(source in token_stream()) {
+        check(&source);
+    }
+
+    #[test]
+    fn strings_do_not_panic(source in any::<String>()) {
+        check(&source);
+    }
+}
+
+#[test]
+fn deep_nesting_is_an_error() {
+    let lists = format!("item_type {{ foo = {} }}", "[".repeat(100_000));
+    let closed_lists = format!(
+        "item_type {{ list = {}{} }}",
+        "[".repeat(100_000),
+        "]".repeat(100_000)
+    );
+    let blocks = "effect { ".repeat(100_000);
+    let ignored_blocks = format!("item_type {{ {}", "unknown { ".repeat(100_000));
+
+    for source in [&lists, &closed_lists, &blocks, &ignored_blocks] {
+        let too_deep =
+            |result: Result<_, Error>| matches!(result, Err(Error::NestingTooDeep { .. }));
+        assert!(too_deep(ast::parse(source).map(drop)));
+        assert!(!ast::parse_recovering(source).1.is_empty());
+        assert!(source.parse::<Document>().is_err());
+        assert!(too_deep(listener::run(source, &mut Ignore)));
+        assert!(Deserializer::from_str_recovering::<Vec<ItemType>>(source)
+            .into_result()
+            .is_err());
+        if source != &blocks {
+            assert!(too_deep(
+                Deserializer::from_str::<ItemType>(source).map(drop)
+            ));
+            assert!(Deserializer::from_str::<AnyItemType>(source).is_err());
+        }
+    }
+}
@@ -0,0 +1,305 @@
+//! Declaring the structure of config files, like libtcod's `TCODParser` does, and checking files
+//! against those declarations without any [`serde`] types.
+//!
+//! This mirrors libtcod's `TCODParser::newStructure`, `TCODParserStruct::addProperty`,
+//! `addFlag`, `addListProperty`, `addValueList` and `addStructure`, so that loaders of games
+//! written against libtcod can be ported line by line:
+//!
+//! ```
+//! use serde_tcod_config_parser::schema::{Parser, Type};
+//!
+//! let mut parser = Parser::new();
+//! parser
+//!     .new_struct("item_type")
+//!     .add_property("cost", Type::Int, true)
+//!     .add_property("weight", Type::Float, false)
+//!     .add_flag("stackable")
+//!     .add_value_list("slot", &["hand", "head", "body"], false)
+//!     .add_list_property("damage", Type::Int, false)
+//!     .add_structure("effect");
+//! parser.new_struct("effect").add_property("power", Type::Float, true);
+//!
+//! let source = r#"
+//!     item_type "Sword" {
+//!         cost = 10
+//!         slot = "hand"
+//!         damage = [2, 5]
+//!         effect { power = 1.5 }
+//!     }
+//! "#;
+//! assert!(parser.validate(source).is_ok());
+//! assert!(parser.validate(r#"item_type "Bow" { weight = 1.5 }"#).is_err());
+//! ```
+//!
+//! Like in libtcod, every declared struct type may be used at the top level of a file, while
+//! struct blocks within other struct blocks must be declared with [`add_structure`].
+//!
+//! Boolean properties (`name = true`) and dice are not supported by the syntax of this crate, so
+//! there is no `Type` for them. Use flags instead of boolean properties.
+//!
+//! [`serde`]: https://crates.io/crates/serde
+//! [`add_structure`]: struct.ParserStruct.html#method.add_structure
+use crate::ast::{self, File};
+use crate::de::{Error, Result};
+use std::fmt::{self, Display};
+
+mod validator;
+
+/// The type of a property.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum Type {
+    /// A char, e.g. `'a'`, or an integer between 0 and 255.
+    Char,
+    /// An integer, e.g. `10` or `0x1F`.
+    Int,
+    /// A floating point number, e.g. `1.5`, or an integer.
+    Float,
+    /// A string, e.g. `"Sword"`.
+    String,
+    /// A color, e.g. `#FF8000`.
+    Color,
+    /// A string that must be one of the given values.
+    ValueList(Vec<String>),
+    /// A list of values of the given type, e.g. `[1, 2, 3]`.
+    List(Box<Type>),
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Char => f.write_str("char"),
+            Type::Int => f.write_str("int"),
+            Type::Float => f.write_str("float"),
+            Type::String => f.write_str("string"),
+            Type::Color => f.write_str("color"),
+            Type::ValueList(values) => {
+                f.write_str("one of ")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "\"{}\"", value)?;
+                }
+                Ok(())
+            }
+            Type::List(element) => write!(f, "list of {}", element),
+        }
+    }
+}
+
+/// A declared property of a struct type.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct PropertyDeclaration {
+    /// The name of the property.
+    pub name: String,
+    /// The type of the property's value.
+    pub property_type: Type,
+    /// Whether every struct block of the type must give the property.
+    pub mandatory: bool,
+}
+
+/// The declaration of a struct type, like libtcod's `TCODParserStruct`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParserStruct {
+    name: String,
+    flags: Vec<String>,
+    properties: Vec<PropertyDeclaration>,
+    structs: Vec<String>,
+}
+
+impl ParserStruct {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            flags: Vec::new(),
+            properties: Vec::new(),
+            structs: Vec::new(),
+        }
+    }
+
+    /// The type name of the struct.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Declares a flag, e.g. `stackable`.
+    pub fn add_flag(&mut self, name: impl Into<String>) -> &mut Self {
+        self.flags.push(name.into());
+        self
+    }
+
+    /// Declares a property of the given type.
+    pub fn add_property(
+        &mut self,
+        name: impl Into<String>,
+        property_type: Type,
+        mandatory: bool,
+    ) -> &mut Self {
+        self.properties.push(PropertyDeclaration {
+            name: name.into(),
+            property_type,
+            mandatory,
+        });
+        self
+    }
+
+    /// Declares a property whose value is a list of values of the given type.
+    pub fn add_list_property(
+        &mut self,
+        name: impl Into<String>,
+        element_type: Type,
+        mandatory: bool,
+    ) -> &mut Self {
+        self.add_property(name, Type::List(Box::new(element_type)), mandatory)
+    }
+
+    /// Declares a string property whose value must be one of `values`.
+    pub fn add_value_list(
+        &mut self,
+        name: impl Into<String>,
+        values: &[&str],
+        mandatory: bool,
+    ) -> &mut Self {
+        let values = values.iter().map(|value| value.to_string()).collect();
+        self.add_property(name, Type::ValueList(values), mandatory)
+    }
+
+    /// Allows struct blocks of the type `name` within struct blocks of this type. The struct type
+    /// itself is declared with [`Parser::new_struct`].
+    ///
+    /// [`Parser::new_struct`]: struct.Parser.html#method.new_struct
+    pub fn add_structure(&mut self, name: impl Into<String>) -> &mut Self {
+        self.structs.push(name.into());
+        self
+    }
+
+    /// The declared flags.
+    pub fn flags(&self) -> &[String] {
+        &self.flags
+    }
+
+    /// The declared properties.
+    pub fn properties(&self) -> &[PropertyDeclaration] {
+        &self.properties
+    }
+
+    /// The type names of the struct blocks allowed within struct blocks of this type.
+    pub fn structures(&self) -> &[String] {
+        &self.structs
+    }
+
+    /// The declaration of the property `name`, if there is one.
+    pub fn property(&self, name: &str) -> Option<&PropertyDeclaration> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+    }
+
+    /// The type of the property `name`, if it is declared.
+    pub fn property_type(&self, name: &str) -> Option<&Type> {
+        self.property(name).map(|property| &property.property_type)
+    }
+
+    /// Whether the property `name` is declared and mandatory.
+    pub fn is_property_mandatory(&self, name: &str) -> bool {
+        self.property(name)
+            .is_some_and(|property| property.mandatory)
+    }
+
+    /// Whether the flag `name` is declared.
+    pub fn has_flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+}
+
+/// A set of struct type declarations that config files can be checked against, like libtcod's
+/// `TCODParser`.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Parser {
+    structs: Vec<ParserStruct>,
+}
+
+impl Parser {
+    /// Creates a parser without any declarations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares a struct type, and returns its declaration so that properties, flags and
+    /// structures can be added to it. Declaring a struct type that is already declared returns
+    /// the existing declaration.
+    pub fn new_struct(&mut self, name: impl Into<String>) -> &mut ParserStruct {
+        let name = name.into();
+        let index = match self.structs.iter().position(|s| s.name == name) {
+            Some(index) => index,
+            None => {
+                self.structs.push(ParserStruct::new(name));
+                self.structs.len() - 1
+            }
+        };
+        &mut self.structs[index]
+    }
+
+    /// The declaration of the struct type `name`, if there is one.
+    pub fn get_struct(&self, name: &str) -> Option<&ParserStruct> {
+        self.structs.iter().find(|s| s.name == name)
+    }
+
+    /// All the declared struct types.
+    pub fn structs(&self) -> &[ParserStruct] {
+        &self.structs
+    }
+
+    /// Parses `source` and checks it against the declarations, returning the first violation.
+    /// See [`validate_all`] to get all of them.
+    ///
+    /// [`validate_all`]: #method.validate_all
+    pub fn validate(&self, source: &str) -> Result<()> {
+        self.validate_file(&ast::parse(source)?)
+    }
+
+    /// Checks an already parsed file against the declarations, returning the first violation.
+    pub fn validate_file(&self, file: &File<'_>) -> Result<()> {
+        match self.validate_file_all(file).into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Parses `source` and checks it against the declarations, returning every violation, so
+    /// that a file can be rejected with one complete report.
+    ///
+    /// Syntax errors are recovered from like [`ast::parse_recovering`] does, and are reported
+    /// first, followed by the violations in the rest of the file, in the order they appear. Every
+    /// violation has the location it was found at. Mandatory properties are not reported missing
+    /// from struct blocks with syntax errors, as they are likely to be what is broken.
+    ///
+    /// ```
+    /// use serde_tcod_config_parser::schema::{Parser, Type};
+    ///
+    /// let mut parser = Parser::new();
+    /// parser
+    ///     .new_struct("monster")
+    ///     .add_property("hp", Type::Int, true)
+    ///     .add_property("name", Type::String, true);
+    ///
+    /// let errors = parser.validate_all(r#"monster "Orc" { hp = "lots" } monster "Elf" { hp = }"#);
+    /// // The Elf's `hp` has no value, and the Orc's has the wrong type and no `name`
+    /// assert_eq!(errors.len(), 3);
+    /// ```
+    ///
+    /// [`ast::parse_recovering`]: ../ast/fn.parse_recovering.html
+    pub fn validate_all(&self, source: &str) -> Vec<Error> {
+        let (file, mut errors) = ast::parse_recovering(source);
+        let violations = validator::Validator::new(self)
+            .broken(&errors)
+            .validate_file(&file);
+        errors.extend(violations);
+        errors
+    }
+
+    /// Checks an already parsed file against the declarations, returning every violation.
+    pub fn validate_file_all(&self, file: &File<'_>) -> Vec<Error> {
+        validator::Validator::new(self).validate_file(file)
+    }
+}
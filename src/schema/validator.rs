@@ -0,0 +1,283 @@
+use crate::ast::{File, Item, StructBlock, Value, ValueKind};
+use crate::de::{suggestions, Error};
+use crate::lexer::Span;
+use crate::schema::{Parser, ParserStruct, Type};
+use std::collections::HashMap;
+
+/// Checks struct blocks against the declarations of a [`Parser`], collecting every violation.
+///
+/// [`Parser`]: struct.Parser.html
+pub(crate) struct Validator<'p> {
+    parser: &'p Parser,
+    errors: Vec<Error>,
+    /// The locations of syntax errors, within which missing properties may have been given.
+    broken: Vec<Span>,
+}
+
+impl<'p> Validator<'p> {
+    pub(crate) fn new(parser: &'p Parser) -> Self {
+        Self {
+            parser,
+            errors: Vec::new(),
+            broken: Vec::new(),
+        }
+    }
+
+    /// Don't report missing properties in struct blocks containing the syntax `errors`, as they
+    /// are likely to be the broken items.
+    pub(crate) fn broken(mut self, errors: &[Error]) -> Self {
+        self.broken = errors.iter().filter_map(Error::range).collect();
+        self
+    }
+
+    /// Checks every struct block of `file`, and returns the violations in the order they appear
+    /// in the file.
+    pub(crate) fn validate_file(mut self, file: &File<'_>) -> Vec<Error> {
+        let parser = self.parser;
+        let names = parser.structs.iter().map(|s| s.name.as_str());
+        for block in &file.structs {
+            self.validate_block(block, names.clone());
+        }
+        // Missing properties are found after the items of their struct block, but are reported
+        // at its type name
+        self.errors
+            .sort_by_key(|error| error.range().map(|range| range.start));
+        self.errors
+    }
+
+    /// Checks a struct block, whose type must be one of `allowed`.
+    fn validate_block<'a>(
+        &mut self,
+        block: &StructBlock<'_>,
+        allowed: impl Iterator<Item = &'a str> + Clone,
+    ) {
+        let declaration = match allowed.clone().find(|&name| name == block.type_name) {
+            Some(name) => self.parser.get_struct(name),
+            None => None,
+        };
+        let declaration = match declaration {
+            Some(declaration) => declaration,
+            None => {
+                self.errors.push(Error::UnknownStruct {
+                    name: block.type_name.to_string(),
+                    did_you_mean: suggestions::closest(block.type_name, allowed),
+                    range: block.type_name_span.clone(),
+                });
+                return;
+            }
+        };
+
+        let mut seen = HashMap::new();
+        for item in &block.items {
+            let (name, span) = match item {
+                Item::Struct(child) => {
+                    let allowed = declaration.structs.iter().map(String::as_str);
+                    self.validate_block(child, allowed);
+                    continue;
+                }
+                Item::Property(property) => (property.name, &property.name_span),
+                Item::Flag(flag) => (flag.name, &flag.span),
+            };
+
+            if let Some(first) = seen.insert(name, span) {
+                self.errors.push(Error::DuplicateProperty {
+                    name: name.to_string(),
+                    first: first.clone(),
+                    second: span.clone(),
+                });
+                continue;
+            }
+
+            match (item, declaration.property_type(name)) {
+                (Item::Property(property), Some(property_type)) => {
+                    check_value(&property.value, property_type, &mut self.errors)
+                }
+                (Item::Flag(_), None) if declaration.has_flag(name) => {}
+                (Item::Flag(flag), Some(property_type)) => self.errors.push(Error::InvalidType {
+                    unexpected: "flag".to_string(),
+                    expected: property_type.to_string(),
+                    range: Some(flag.span.clone()),
+                }),
+                (Item::Property(property), None) if declaration.has_flag(name) => {
+                    self.errors.push(Error::InvalidType {
+                        unexpected: property.value.kind.unexpected().to_string(),
+                        expected: "flag".to_string(),
+                        range: Some(property.value.span.clone()),
+                    })
+                }
+                _ => self.errors.push(unknown_property(declaration, name, span)),
+            }
+        }
+
+        let span = &block.span;
+        if self
+            .broken
+            .iter()
+            .any(|broken| span.start <= broken.start && broken.end <= span.end)
+        {
+            return;
+        }
+
+        for missing in declaration
+            .properties
+            .iter()
+            .filter(|property| property.mandatory && !seen.contains_key(property.name.as_str()))
+        {
+            self.errors.push(Error::MissingProperty {
+                name: missing.name.clone(),
+                struct_name: declaration.name.clone(),
+                range: block.type_name_span.clone(),
+            });
+        }
+    }
+}
+
+fn unknown_property(
+    declaration: &ParserStruct,
+    name: &str,
+    span: &std::ops::Range<usize>,
+) -> Error {
+    let names = declaration
+        .properties
+        .iter()
+        .map(|property| property.name.as_str())
+        .chain(declaration.flags.iter().map(String::as_str));
+    Error::UnknownProperty {
+        name: name.to_string(),
+        struct_name: declaration.name.clone(),
+        did_you_mean: suggestions::closest(name, names),
+        range: span.clone(),
+    }
+}
+
+/// Checks that `value` is of type `expected`, adding the violations to `errors`.
+fn check_value(value: &Value<'_>, expected: &Type, errors: &mut Vec<Error>) {
+    let matches = match (&value.kind, expected) {
+        (ValueKind::Char(_), Type::Char)
+        | (ValueKind::Integer(_), Type::Int)
+        | (ValueKind::Integer(_), Type::Float)
+        | (ValueKind::Float(_), Type::Float)
+        | (ValueKind::String(_), Type::String)
+        | (ValueKind::Color(_), Type::Color) => true,
+        (ValueKind::Integer(value), Type::Char) => (0..=255).contains(value),
+        (ValueKind::String(string), Type::ValueList(values)) => {
+            if !values.iter().any(|value| value == string) {
+                errors.push(Error::InvalidValue {
+                    unexpected: value.kind.unexpected().to_string(),
+                    expected: expected.to_string(),
+                    range: Some(value.span.clone()),
+                });
+            }
+            true
+        }
+        (ValueKind::List(list), Type::List(element)) => {
+            for value in &list.values {
+                check_value(value, element, errors);
+            }
+            true
+        }
+        _ => false,
+    };
+
+    if !matches {
+        errors.push(Error::InvalidType {
+            unexpected: value.kind.unexpected().to_string(),
+            expected: expected.to_string(),
+            range: Some(value.span.clone()),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::de::Error;
+    use crate::schema::{Parser, Type};
+
+    fn parser() -> Parser {
+        let mut parser = Parser::new();
+        parser
+            .new_struct("item_type")
+            .add_property("cost", Type::Int, true)
+            .add_flag("stackable")
+            .add_value_list("slot", &["hand", "head"], false)
+            .add_list_property("damage", Type::Int, false)
+            .add_structure("effect");
+        parser
+            .new_struct("effect")
+            .add_property("power", Type::Float, true);
+        parser
+    }
+
+    #[test]
+    fn violations() {
+        let parser = parser();
+        let validate = |source| parser.validate(source).unwrap_err();
+
+        assert!(matches!(
+            validate("item_type \"Sword\" { cost = 10 stackable effect { } }"),
+            Error::MissingProperty { name, struct_name, range }
+                if name == "power" && struct_name == "effect" && range == (40..46)
+        ));
+        assert!(matches!(
+            validate("item_type { cost = 10 cots = 10 }"),
+            Error::UnknownProperty { name, did_you_mean, .. }
+                if name == "cots" && did_you_mean == ["cost"]
+        ));
+        assert!(matches!(
+            validate("item_type { cost = 10 damage = [1, \"2\"] }"),
+            Error::InvalidType { expected, range, .. }
+                if expected == "int" && range == Some(35..38)
+        ));
+        assert!(matches!(
+            validate("item_type { cost = 10 slot = \"foot\" }"),
+            Error::InvalidValue { expected, .. } if expected == "one of \"hand\", \"head\""
+        ));
+        assert!(matches!(
+            validate("effect { power = 1 } item_type { cost = 1 item_type { cost = 2 } }"),
+            Error::UnknownStruct { name, .. } if name == "item_type"
+        ));
+        assert!(matches!(
+            validate("item_type { cost stackable }"),
+            Error::InvalidType { expected, .. } if expected == "int"
+        ));
+    }
+
+    #[test]
+    fn full_report() {
+        let source = "item_type \"Sword\" {
+  cost = \"ten\"
+  slot = \"foot\"
+  damage = [1, 2.5, 'x']
+  sharp
+  cost = 2
+  effect { }
+  item_type { cost = 1 }
+}
+item_type \"Bow\" { cost = }
+weapon { }";
+        let errors = parser().validate_all(source);
+        let codes = errors.iter().map(Error::code).collect::<Vec<_>>();
+        assert_eq!(
+            codes,
+            [
+                "TCOD0002", "TCOD0014", "TCOD0015", "TCOD0014", "TCOD0014", "TCOD0020", "TCOD0005",
+                "TCOD0021", "TCOD0019", "TCOD0019",
+            ]
+        );
+        assert!(errors.iter().all(|error| error.range().is_some()));
+    }
+
+    #[test]
+    fn violations_in_order() {
+        let errors = parser().validate_all("item_type { effect { } stackable = 1 }");
+        let names = errors
+            .iter()
+            .map(|error| match error {
+                Error::MissingProperty { name, .. } => name.as_str(),
+                Error::InvalidType { .. } => "stackable",
+                error => panic!("unexpected error: {:?}", error),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["cost", "power", "stackable"]);
+    }
+}
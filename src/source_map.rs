@@ -0,0 +1,278 @@
+//! A map from the things in a config file back to where they were given.
+//!
+//! Paths look like `monster["Orc"].attacks[1].damage`: struct blocks with an instance name are
+//! addressed by `type_name["instance_name"]`, struct blocks without one by their index among the
+//! struct blocks of the same type, e.g. `attacks[1]` (the first one can also be addressed as just
+//! `attacks`), and list elements by their index, e.g. `monster["Orc"].resistances[0]`. Properties
+//! and flags are addressed by their name.
+//!
+//! ```
+//! use serde_derive::Deserialize;
+//! use serde_tcod_config_parser::de::Deserializer;
+//!
+//! #[derive(Deserialize)]
+//! #[serde(rename = "monster")]
+//! struct Monster {
+//!     instance_name: String,
+//!     hp: i32,
+//! }
+//!
+//! let (orc, source_map) =
+//!     Deserializer::from_str_with_source_map::<Monster>("monster \"Orc\" {\n  hp = -5\n}").unwrap();
+//! if orc.hp < 0 {
+//!     let location = source_map.get("monster[\"Orc\"].hp").unwrap();
+//!     assert_eq!(location.start.line, 2);
+//! }
+//! ```
+use crate::ast::{File, Item, StructBlock, Value, ValueKind};
+use crate::lexer::{Position, Span};
+use std::collections::{BTreeMap, HashMap};
+use std::iter;
+
+/// Where something was given in a config file.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Location {
+    /// The name of the file, if known.
+    pub file: Option<String>,
+    /// The location, as a range of byte offsets.
+    pub span: Span,
+    /// The line and column the location starts at.
+    pub start: Position,
+    /// The line and column the location ends at, i.e. just past its last character.
+    pub end: Position,
+}
+
+/// A map from the paths of struct blocks, properties, flags and list elements to their locations.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct SourceMap {
+    locations: BTreeMap<String, Location>,
+    /// The paths of the first struct blocks without an instance name, like `attacks`, resolving to
+    /// the paths by index, like `attacks[0]`.
+    aliases: HashMap<String, String>,
+}
+
+impl SourceMap {
+    /// Builds the source map of a parsed file.
+    pub fn new(file: &File<'_>) -> Self {
+        let mut builder = Builder {
+            source: file.source,
+            line_starts: iter::once(0)
+                .chain(file.source.match_indices('\n').map(|(index, _)| index + 1))
+                .collect(),
+            locations: BTreeMap::new(),
+            aliases: HashMap::new(),
+        };
+        builder.add_blocks("", file.structs.iter());
+
+        Self {
+            locations: builder.locations,
+            aliases: builder.aliases,
+        }
+    }
+
+    /// Sets the name of the file for every location.
+    pub fn with_file_name(mut self, file_name: impl Into<String>) -> Self {
+        let file_name = file_name.into();
+        for location in self.locations.values_mut() {
+            location.file = Some(file_name.clone());
+        }
+        self
+    }
+
+    /// The location of the thing at `path`, if there is one.
+    pub fn get(&self, path: &str) -> Option<&Location> {
+        self.locations
+            .get(path)
+            .or_else(|| self.locations.get(&self.resolve_aliases(path)))
+    }
+
+    /// Replaces the paths of struct blocks addressed without an index within `path` with their
+    /// paths by index, from left to right.
+    fn resolve_aliases(&self, path: &str) -> String {
+        let mut resolved = String::new();
+        let mut start = 0;
+        let mut quoted = false;
+        for (index, c) in path.char_indices().chain(iter::once((path.len(), '.'))) {
+            match c {
+                '"' => quoted = !quoted,
+                '.' if !quoted => {
+                    resolved.push_str(&path[start..index]);
+                    if let Some(alias) = self.aliases.get(&resolved) {
+                        resolved.clone_from(alias);
+                    }
+                    start = index;
+                }
+                _ => {}
+            }
+        }
+        resolved
+    }
+
+    /// All the paths and their locations, ordered by path. Struct blocks without an instance name
+    /// are only listed by index.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Location)> {
+        self.locations
+            .iter()
+            .map(|(path, location)| (path.as_str(), location))
+    }
+
+    /// The number of paths in the map.
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    /// Whether the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+}
+
+struct Builder<'a> {
+    source: &'a str,
+    /// The byte offsets the lines of the source start at.
+    line_starts: Vec<usize>,
+    locations: BTreeMap<String, Location>,
+    aliases: HashMap<String, String>,
+}
+
+impl<'a> Builder<'a> {
+    fn insert(&mut self, path: String, span: &Span) {
+        let location = Location {
+            file: None,
+            span: span.clone(),
+            start: self.position(span.start),
+            end: self.position(span.end),
+        };
+        self.locations.insert(path, location);
+    }
+
+    /// Finds the line and column of the byte `offset`, like [`Position::of`].
+    ///
+    /// [`Position::of`]: ../lexer/struct.Position.html#method.of
+    fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.source.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        Position {
+            line,
+            column: self.source[line_start..offset].chars().count() + 1,
+        }
+    }
+
+    fn add_blocks<'b>(&mut self, prefix: &str, blocks: impl Iterator<Item = &'b StructBlock<'b>>) {
+        let mut indices = HashMap::new();
+        for block in blocks {
+            let path = match block.instance_name {
+                Some(instance_name) => {
+                    format!("{}{}[\"{}\"]", prefix, block.type_name, instance_name)
+                }
+                None => {
+                    let index = indices.entry(block.type_name).or_insert(0);
+                    let path = format!("{}{}[{}]", prefix, block.type_name, index);
+                    if *index == 0 {
+                        let alias = format!("{}{}", prefix, block.type_name);
+                        self.aliases.insert(alias, path.clone());
+                    }
+                    *index += 1;
+                    path
+                }
+            };
+
+            self.insert(path.clone(), &block.span);
+            self.add_items(&path, &block.items);
+        }
+    }
+
+    fn add_items<'b>(&mut self, path: &str, items: &'b [Item<'b>]) {
+        for item in items {
+            match item {
+                Item::Property(property) => {
+                    self.add_value(format!("{}.{}", path, property.name), &property.value)
+                }
+                Item::Flag(flag) => self.insert(format!("{}.{}", path, flag.name), &flag.span),
+                Item::Struct(_) => {}
+            }
+        }
+
+        let blocks = items.iter().filter_map(|item| match item {
+            Item::Struct(block) => Some(block),
+            _ => None,
+        });
+        self.add_blocks(&format!("{}.", path), blocks);
+    }
+
+    fn add_value(&mut self, path: String, value: &Value<'_>) {
+        if let ValueKind::List(list) = &value.kind {
+            for (index, value) in list.values.iter().enumerate() {
+                self.add_value(format!("{}[{}]", path, index), value);
+            }
+        }
+        self.insert(path, &value.span);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast;
+
+    #[test]
+    fn paths() {
+        let source = "monster \"Orc\" {
+  resistances = [1, 2]
+  undead
+  attacks { damage = 3 }
+  attacks { damage = 5 }
+}
+monster { hp = 1 }";
+        let source_map = SourceMap::new(&ast::parse(source).unwrap()).with_file_name("orc.cfg");
+
+        let damage = source_map
+            .get("monster[\"Orc\"].attacks[1].damage")
+            .unwrap();
+        assert_eq!(&source[damage.span.clone()], "5");
+        assert_eq!((damage.start.line, damage.start.column), (5, 22));
+        assert_eq!(damage.file.as_deref(), Some("orc.cfg"));
+
+        let first = source_map.get("monster[\"Orc\"].attacks").unwrap();
+        assert_eq!(source_map.get("monster[\"Orc\"].attacks[0]"), Some(first));
+
+        let resistance = source_map.get("monster[\"Orc\"].resistances[1]").unwrap();
+        assert_eq!(&source[resistance.span.clone()], "2");
+        assert!(source_map.get("monster[\"Orc\"].undead").is_some());
+        assert!(source_map.get("monster[0].hp").is_some());
+        assert!(source_map.get("monster.hp").is_some());
+        assert_eq!(
+            source_map.get("monster.hp"),
+            source_map.get("monster[0].hp")
+        );
+    }
+
+    #[test]
+    fn nested_blocks_without_instance_names() {
+        let depth = 20;
+        let source = format!("{}x = 1{}", "a { ".repeat(depth), " }".repeat(depth));
+        let source_map = SourceMap::new(&ast::parse(&source).unwrap());
+
+        assert_eq!(source_map.len(), depth + 1);
+        let x = source_map.get(&format!("{}x", "a.".repeat(depth))).unwrap();
+        assert_eq!(&source[x.span.clone()], "1");
+        assert_eq!(
+            source_map.get(&format!("{}x", "a[0].".repeat(depth))),
+            Some(x)
+        );
+    }
+
+    #[test]
+    fn positions() {
+        let source = "monster \"Orc\" {\n  hp = 1\n}\n\nmonster \"Élf\" {\n  hp = 2 }";
+        let source_map = SourceMap::new(&ast::parse(source).unwrap());
+
+        for (_, location) in source_map.iter() {
+            assert_eq!(location.start, Position::of(source, location.span.start));
+            assert_eq!(location.end, Position::of(source, location.span.end));
+        }
+        let hp = source_map.get("monster[\"Élf\"].hp").unwrap();
+        assert_eq!((hp.start.line, hp.start.column), (6, 8));
+    }
+}